@@ -0,0 +1,491 @@
+//! Offline snapshot of the PokéAPI species data.
+//!
+//! Air-gapped deployments can't reach pokeapi.co, so the species data can be loaded from a local
+//! directory at startup instead, either as species JSON documents or as the official CSV dump.
+//! Both are assembled into the documents the PokéAPI would return, i.e. species, generations,
+//! habitats, varieties and evolution chains, so that `PokeApi` can serve them with the same
+//! response models.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::ApiError;
+use crate::config::{DatasetConfig, DatasetFormat};
+
+/// The PokéAPI documents of a local snapshot by their path, e.g. `api/v2/pokemon-species/charizard`.
+pub struct Dataset {
+    root: Url,
+    species: Vec<String>,
+    varieties: Vec<String>,
+    documents: HashMap<String, Value>,
+}
+
+impl Dataset {
+    /// Load the snapshot configured in the `dataset` section.
+    pub fn load(config: &DatasetConfig) -> anyhow::Result<Self> {
+        let path = config
+            .path
+            .canonicalize()
+            .with_context(|| format!("Failed to open dataset {}", config.path.display()))?;
+        let species = match config.format {
+            DatasetFormat::Json => load_json(&path)?,
+            DatasetFormat::Csv => load_csv(&path)?,
+        };
+        anyhow::ensure!(
+            !species.is_empty(),
+            "No species found in dataset {}",
+            path.display()
+        );
+        let root = Url::from_directory_path(&path)
+            .map_err(|_| anyhow::anyhow!("Invalid dataset path {}", path.display()))?;
+        Self::from_species(root, species)
+    }
+
+    /// Assemble all documents derived from the species, e.g. their generations.
+    fn from_species(root: Url, documents: Vec<Value>) -> anyhow::Result<Self> {
+        let mut dataset = Dataset {
+            root,
+            species: Vec::with_capacity(documents.len()),
+            varieties: Vec::new(),
+            documents: HashMap::new(),
+        };
+        let mut summaries = Vec::with_capacity(documents.len());
+        for document in documents {
+            let summary: SpeciesSummary =
+                serde_json::from_value(document.clone()).context("Invalid species document")?;
+            dataset.insert(PokeApi::SPECIES, &summary.id.to_string(), document.clone());
+            dataset.insert(PokeApi::SPECIES, &summary.name, document);
+            summaries.push(summary);
+        }
+        summaries.sort_by_key(|species| species.id);
+        dataset.species = summaries.iter().map(|s| s.name.clone()).collect();
+
+        let mut generations = BTreeMap::<_, Vec<_>>::new();
+        let mut generation_ids = HashMap::new();
+        let mut habitats = BTreeMap::<_, Vec<_>>::new();
+        let mut chains = BTreeMap::<_, Vec<_>>::new();
+        for species in &summaries {
+            generations
+                .entry(species.generation.name.as_str())
+                .or_default()
+                .push(json!({ "name": species.name }));
+            // generations are looked up by ID as well, e.g. `1` for `generation-i`
+            if let Ok(id) = last_segment(&species.generation.url) {
+                generation_ids.insert(species.generation.name.as_str(), id);
+            }
+            if let Some(habitat) = &species.habitat {
+                habitats
+                    .entry(habitat.name.as_str())
+                    .or_default()
+                    .push(json!({ "name": species.name }));
+            }
+            if let Some(chain) = &species.evolution_chain {
+                chains
+                    .entry(last_segment(&chain.url)?)
+                    .or_default()
+                    .push(species);
+            }
+            for variety in &species.varieties {
+                let document = json!({
+                    "id": last_segment(&variety.pokemon.url)?,
+                    "name": variety.pokemon.name,
+                    "is_default": variety.is_default,
+                    "species": { "name": species.name },
+                    "forms": [],
+                });
+                dataset.insert(PokeApi::POKEMON, &variety.pokemon.name, document);
+                dataset.varieties.push(variety.pokemon.name.clone());
+            }
+        }
+        for (generation, species) in generations {
+            let document = json!({ "pokemon_species": species });
+            if let Some(id) = generation_ids.get(generation) {
+                dataset.insert(PokeApi::GENERATION, &id.to_string(), document.clone());
+            }
+            dataset.insert(PokeApi::GENERATION, generation, document);
+        }
+        for (habitat, species) in habitats {
+            dataset.insert(
+                PokeApi::HABITAT,
+                habitat,
+                json!({ "pokemon_species": species }),
+            );
+        }
+        for (id, species) in chains {
+            // the base stage is the only one not evolving from another species of the chain
+            let base = species.iter().find(|s| {
+                s.evolves_from_species
+                    .as_ref()
+                    .is_none_or(|from| species.iter().all(|s| s.name != from.name))
+            });
+            if let Some(base) = base {
+                let document = json!({ "id": id, "chain": chain_link(base, &species) });
+                dataset.insert(PokeApi::EVOLUTION_CHAIN, &id.to_string(), document);
+            }
+        }
+        Ok(dataset)
+    }
+
+    fn insert(&mut self, endpoint: &str, name: &str, document: Value) {
+        self.documents
+            .insert(format!("{}{}", endpoint, name), document);
+    }
+
+    /// Get a resource of the given endpoint by its name or ID.
+    pub fn get<T: DeserializeOwned>(&self, endpoint: &str, name: &str) -> Result<T, ApiError> {
+        let path = format!("{}{}", endpoint, name);
+        match self.documents.get(&path) {
+            Some(document) => Ok(T::deserialize(document)?),
+            None => Err(ApiError::NotInDataset(path)),
+        }
+    }
+
+    /// The names of all species, ordered by their National Pokédex number.
+    pub fn species_names(&self) -> &[String] {
+        &self.species
+    }
+
+    /// The names of all varieties, e.g. `charizard-mega-x`.
+    pub fn variety_names(&self) -> &[String] {
+        &self.varieties
+    }
+
+    /// The `file://` URL of the snapshot directory.
+    pub fn root(&self) -> &Url {
+        &self.root
+    }
+}
+
+impl fmt::Debug for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dataset")
+            .field("root", &self.root.as_str())
+            .field("species", &self.species.len())
+            .finish()
+    }
+}
+
+/// The fields of a species document relevant for the derived documents.
+#[derive(Deserialize, Debug)]
+struct SpeciesSummary {
+    id: u32,
+    name: String,
+    generation: Resource,
+    habitat: Option<Resource>,
+    evolves_from_species: Option<Resource>,
+    evolution_chain: Option<Resource>,
+    varieties: Vec<VarietySummary>,
+}
+
+#[derive(Deserialize, Debug)]
+struct VarietySummary {
+    is_default: bool,
+    pokemon: Resource,
+}
+
+#[derive(Deserialize, Debug)]
+struct Resource {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    url: String,
+}
+
+/// Parse the ID at the end of a resource URL, e.g. `2` of `.../evolution-chain/2/`.
+fn last_segment(url: &str) -> anyhow::Result<u32> {
+    url.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .with_context(|| format!("No resource ID in {}", url))
+}
+
+/// Build the link of a species and all later stages of its evolution chain.
+fn chain_link(species: &SpeciesSummary, chain: &[&SpeciesSummary]) -> Value {
+    let evolves_to = chain
+        .iter()
+        .filter(|s| {
+            s.evolves_from_species
+                .as_ref()
+                .is_some_and(|from| from.name == species.name)
+        })
+        .map(|s| chain_link(s, chain))
+        .collect::<Vec<_>>();
+    json!({ "species": { "name": species.name }, "evolves_to": evolves_to })
+}
+
+/// Load all species documents within the directory and its subdirectories.
+///
+/// Documents without a species, e.g. the paginated index of a mirrored API, are skipped.
+fn load_json(dir: &Path) -> anyhow::Result<Vec<Value>> {
+    let mut species = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read dataset directory {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                let file = File::open(&path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                let document: Value = serde_json::from_reader(std::io::BufReader::new(file))
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                if serde_json::from_value::<SpeciesSummary>(document.clone()).is_ok() {
+                    species.push(document);
+                } else {
+                    tracing::debug!("Skipping {}, it is no species", path.display());
+                }
+            }
+        }
+    }
+    Ok(species)
+}
+
+/// Assemble the species documents from the CSV dump the PokéAPI database is built from, i.e.
+/// the `data/v2/csv` directory of its repository.
+fn load_csv(dir: &Path) -> anyhow::Result<Vec<Value>> {
+    let languages = identifiers(dir, "languages.csv")?;
+    let versions = identifiers(dir, "versions.csv")?;
+    let generations = identifiers(dir, "generations.csv")?;
+    let habitats = identifiers(dir, "pokemon_habitats.csv")?;
+    let colors = identifiers(dir, "pokemon_colors.csv")?;
+    let shapes = identifiers(dir, "pokemon_shapes.csv")?;
+    let lookup = |table: &HashMap<u32, String>, id: u32, file: &str| {
+        table
+            .get(&id)
+            .cloned()
+            .with_context(|| format!("Unknown ID {} of {}", id, file))
+    };
+
+    let mut names = HashMap::<_, Vec<_>>::new();
+    let mut genera = HashMap::<_, Vec<_>>::new();
+    for row in read_csv::<NameRow>(dir, "pokemon_species_names.csv")? {
+        let language =
+            json!({ "name": lookup(&languages, row.local_language_id, "languages.csv")? });
+        if !row.genus.is_empty() {
+            genera
+                .entry(row.pokemon_species_id)
+                .or_default()
+                .push(json!({ "genus": row.genus, "language": language }));
+        }
+        names
+            .entry(row.pokemon_species_id)
+            .or_default()
+            .push(json!({ "name": row.name, "language": language }));
+    }
+    let mut flavor_texts = HashMap::<_, Vec<_>>::new();
+    for row in read_csv::<FlavorTextRow>(dir, "pokemon_species_flavor_text.csv")? {
+        flavor_texts.entry(row.species_id).or_default().push(json!({
+            "flavor_text": row.flavor_text,
+            "language": { "name": lookup(&languages, row.language_id, "languages.csv")? },
+            "version": { "name": lookup(&versions, row.version_id, "versions.csv")? },
+        }));
+    }
+    let mut varieties = HashMap::<_, Vec<_>>::new();
+    for row in read_csv::<PokemonRow>(dir, "pokemon.csv")? {
+        varieties.entry(row.species_id).or_default().push(json!({
+            "is_default": row.is_default == 1,
+            "pokemon": { "name": row.identifier, "url": format!("{}{}/", PokeApi::POKEMON, row.id) },
+        }));
+    }
+
+    let rows = read_csv::<SpeciesRow>(dir, "pokemon_species.csv")?;
+    let identifiers = rows
+        .iter()
+        .map(|row| (row.id, row.identifier.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut species = Vec::with_capacity(rows.len());
+    for row in rows {
+        let evolves_from_species = row
+            .evolves_from_species_id
+            .map(|id| lookup(&identifiers, id, "pokemon_species.csv"))
+            .transpose()?;
+        let habitat = row
+            .habitat_id
+            .map(|id| lookup(&habitats, id, "pokemon_habitats.csv"))
+            .transpose()?;
+        let shape = row
+            .shape_id
+            .map(|id| lookup(&shapes, id, "pokemon_shapes.csv"))
+            .transpose()?;
+        species.push(json!({
+            "id": row.id,
+            "name": row.identifier,
+            "names": names.remove(&row.id).unwrap_or_default(),
+            "genera": genera.remove(&row.id).unwrap_or_default(),
+            "generation": {
+                "name": lookup(&generations, row.generation_id, "generations.csv")?,
+                "url": format!("{}{}/", PokeApi::GENERATION, row.generation_id),
+            },
+            "habitat": habitat.map(|name| json!({ "name": name })),
+            "color": { "name": lookup(&colors, row.color_id, "pokemon_colors.csv")? },
+            "shape": shape.map(|name| json!({ "name": name })),
+            "is_legendary": row.is_legendary == 1,
+            "is_mythical": row.is_mythical == 1,
+            "evolves_from_species": evolves_from_species.map(|name| json!({ "name": name })),
+            "evolution_chain": row
+                .evolution_chain_id
+                .map(|id| json!({ "url": format!("{}{}/", PokeApi::EVOLUTION_CHAIN, id) })),
+            "varieties": varieties.remove(&row.id).unwrap_or_default(),
+            "flavor_text_entries": flavor_texts.remove(&row.id).unwrap_or_default(),
+        }));
+    }
+    Ok(species)
+}
+
+fn read_csv<T: DeserializeOwned>(dir: &Path, file: &str) -> anyhow::Result<Vec<T>> {
+    let path: PathBuf = dir.join(file);
+    csv::Reader::from_path(&path)
+        .and_then(|mut reader| reader.deserialize().collect())
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Read a table mapping IDs to identifiers, e.g. `languages.csv`.
+fn identifiers(dir: &Path, file: &str) -> anyhow::Result<HashMap<u32, String>> {
+    Ok(read_csv::<IdentifierRow>(dir, file)?
+        .into_iter()
+        .map(|row| (row.id, row.identifier))
+        .collect())
+}
+
+#[derive(Deserialize, Debug)]
+struct IdentifierRow {
+    id: u32,
+    identifier: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpeciesRow {
+    id: u32,
+    identifier: String,
+    generation_id: u32,
+    evolves_from_species_id: Option<u32>,
+    evolution_chain_id: Option<u32>,
+    color_id: u32,
+    shape_id: Option<u32>,
+    habitat_id: Option<u32>,
+    is_legendary: u8,
+    is_mythical: u8,
+}
+
+#[derive(Deserialize, Debug)]
+struct NameRow {
+    pokemon_species_id: u32,
+    local_language_id: u32,
+    name: String,
+    genus: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct FlavorTextRow {
+    species_id: u32,
+    version_id: u32,
+    language_id: u32,
+    flavor_text: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PokemonRow {
+    id: u32,
+    identifier: String,
+    species_id: u32,
+    is_default: u8,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::api_clients::pokeapi::{
+        EvolutionChainResponse, FlavorTexts, PokeApi, PokemonResponse, PokemonSpeciesResponse,
+    };
+    use crate::config::{DatasetConfig, DatasetFormat};
+
+    use super::Dataset;
+
+    fn load(format: DatasetFormat, dir: &str) -> Dataset {
+        Dataset::load(&DatasetConfig {
+            path: format!("{}/testdata/dataset/{}", env!("CARGO_MANIFEST_DIR"), dir).into(),
+            format,
+        })
+        .unwrap()
+    }
+
+    fn assert_charizard_line(dataset: &Dataset) {
+        assert_eq!(
+            dataset.species_names(),
+            ["charmander", "charmeleon", "charizard"]
+        );
+
+        let charizard: PokemonSpeciesResponse = dataset.get(PokeApi::SPECIES, "charizard").unwrap();
+        assert_eq!(charizard.english_genus(), Some("Flame Pokémon"));
+        assert_eq!(charizard.habitat(), Some("mountain"));
+        assert!(charizard.english_flavor_text_entries().count() > 0);
+        let by_id: PokemonSpeciesResponse = dataset.get(PokeApi::SPECIES, "6").unwrap();
+        assert_eq!(by_id, charizard);
+
+        assert!(dataset
+            .variety_names()
+            .contains(&"charizard-mega-x".to_string()));
+        let mega_x: PokemonResponse = dataset.get(PokeApi::POKEMON, "charizard-mega-x").unwrap();
+        assert_eq!(mega_x.species(), "charizard");
+
+        let generation: serde_json::Value =
+            dataset.get(PokeApi::GENERATION, "generation-i").unwrap();
+        assert_eq!(generation["pokemon_species"].as_array().unwrap().len(), 3);
+        let by_id: serde_json::Value = dataset.get(PokeApi::GENERATION, "1").unwrap();
+        assert_eq!(by_id, generation);
+
+        let chain: EvolutionChainResponse = dataset
+            .get(
+                PokeApi::EVOLUTION_CHAIN,
+                &charizard.evolution_chain_id().unwrap().to_string(),
+            )
+            .unwrap();
+        let stages = chain
+            .stages()
+            .into_iter()
+            .map(|stage| (stage.name, stage.evolves_from))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            [
+                ("charmander", None),
+                ("charmeleon", Some("charmander")),
+                ("charizard", Some("charmeleon")),
+            ]
+        );
+
+        let err = dataset
+            .get::<PokemonSpeciesResponse>(PokeApi::SPECIES, "missingno")
+            .unwrap_err();
+        assert!(err.is_not_found());
+    }
+
+    #[test]
+    fn test_json_dataset() {
+        assert_charizard_line(&load(DatasetFormat::Json, "json"));
+    }
+
+    #[test]
+    fn test_csv_dataset() {
+        let dataset = load(DatasetFormat::Csv, "csv");
+        assert_charizard_line(&dataset);
+
+        let charizard: PokemonSpeciesResponse = dataset.get(PokeApi::SPECIES, "charizard").unwrap();
+        assert_eq!(charizard.english_name(), Some("Charizard"));
+        let versions = charizard
+            .english_flavor_text_entries()
+            .map(|e| e.version())
+            .collect::<Vec<_>>();
+        assert_eq!(versions, ["red", "blue"]);
+    }
+}
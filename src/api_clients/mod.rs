@@ -1,3 +1,4 @@
+pub mod dataset;
 pub mod pokeapi;
 pub mod shakespeare;
 
@@ -8,8 +9,37 @@ pub enum ApiError {
     JsonDecoding(#[from] serde_json::Error),
     #[error(transparent)]
     RateLimit(reqwest::Error),
+    #[error("Translation quota is exhausted for another {0:?}")]
+    QuotaExhausted(std::time::Duration),
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
     #[error(transparent)]
     Url(#[from] url::ParseError),
+    #[error("{0} is not part of the offline dataset")]
+    NotInDataset(String),
+}
+
+impl ApiError {
+    /// Short, stable name of the error kind, e.g. for metric labels.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ApiError::JsonDecoding(_) => "json_decoding",
+            ApiError::RateLimit(_) => "rate_limit",
+            ApiError::QuotaExhausted(_) => "quota_exhausted",
+            ApiError::Reqwest(e) if e.is_timeout() => "timeout",
+            ApiError::Reqwest(e) if e.is_status() => "status",
+            ApiError::Reqwest(_) => "request",
+            ApiError::Url(_) => "url",
+            ApiError::NotInDataset(_) => "not_found",
+        }
+    }
+
+    /// Whether the upstream API doesn't know the requested resource.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            ApiError::Reqwest(e) => e.status() == Some(reqwest::StatusCode::NOT_FOUND),
+            ApiError::NotInDataset(_) => true,
+            _ => false,
+        }
+    }
 }
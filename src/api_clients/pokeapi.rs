@@ -1,29 +1,71 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use reqwest::Url;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 
+use crate::api_clients::dataset::Dataset;
 use crate::api_clients::ApiError;
-use crate::config::PokeApiUrl;
+use crate::config::{Config, PokeApiUrl};
+use crate::metrics;
+use crate::telemetry;
 
 /// API Client for pokeapi.co
 ///
-/// Currently only a subset of the data for the `pokemon-species` is supported, i.e. only
-/// information relevant for english flavor texts is kept.
+/// Currently only a subset of the data for the `pokemon-species` is supported, i.e. the
+/// flavor texts and the Pokédex metadata of a species.
+///
+/// With an offline dataset, species are looked up in the local snapshot instead and all other
+/// resources, e.g. moves, are unknown.
 #[derive(Clone, Debug)]
 pub struct PokeApi {
     client: reqwest::Client,
     base_url: Url,
+    dataset: Option<Arc<Dataset>>,
 }
 
 impl PokeApi {
-    const SPECIES: &'static str = "api/v2/pokemon-species/";
+    const CLIENT: &'static str = "PokeApi";
+    const API_ROOT: &'static str = "api/v2/";
+    pub(crate) const SPECIES: &'static str = "api/v2/pokemon-species/";
+    pub(crate) const GENERATION: &'static str = "api/v2/generation/";
+    pub(crate) const HABITAT: &'static str = "api/v2/pokemon-habitat/";
+    pub(crate) const EVOLUTION_CHAIN: &'static str = "api/v2/evolution-chain/";
+    pub(crate) const POKEMON: &'static str = "api/v2/pokemon/";
+    const POKEMON_FORM: &'static str = "api/v2/pokemon-form/";
+    const MOVE: &'static str = "api/v2/move/";
+    const ABILITY: &'static str = "api/v2/ability/";
+    const ITEM: &'static str = "api/v2/item/";
+    const BERRY: &'static str = "api/v2/berry/";
+    /// Large enough to list all species or varieties in a single page.
+    const LIST_LIMIT: &'static str = "100000";
     /// Construct a new PokeAPI client.
     pub fn new(base_url: PokeApiUrl) -> Self {
         PokeApi {
             client: reqwest::Client::new(),
             base_url: base_url.0,
+            dataset: None,
+        }
+    }
+
+    /// Construct a client serving the species of a local snapshot without any network access.
+    pub fn offline(dataset: Dataset) -> Self {
+        PokeApi {
+            client: reqwest::Client::new(),
+            base_url: dataset.root().clone(),
+            dataset: Some(Arc::new(dataset)),
         }
     }
 
+    /// Construct the client of the config, offline if a `dataset` is configured.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        Ok(match &config.dataset {
+            Some(dataset) => Self::offline(Dataset::load(dataset)?),
+            None => Self::new(config.poke_api_base_url.clone()),
+        })
+    }
+
     /// Makes a call to the Pokemon Species Endpoint.
     ///
     /// The returned object only contains the fields relevant for the Shakespeareation service.
@@ -32,17 +74,154 @@ impl PokeApi {
         &self,
         pokemon: &str,
     ) -> Result<PokemonSpeciesResponse, ApiError> {
+        self.get_named(Self::SPECIES, pokemon).await
+    }
+
+    /// Get the species of a Pokémon by its species or variety name, e.g. `charizard-mega-x`.
+    ///
+    /// Species names are tried first, only unknown names are looked up as varieties.
+    #[tracing::instrument(name = "Resolve pokemon species", skip(self))]
+    pub async fn resolve_species(&self, name: &str) -> Result<PokemonSpeciesResponse, ApiError> {
+        match self.get_pokemon_species_description(name).await {
+            Err(e) if e.is_not_found() => {
+                let variety = self.get_pokemon(name).await?;
+                self.get_pokemon_species_description(variety.species())
+                    .await
+            }
+            species => species,
+        }
+    }
+
+    /// Makes a call to the Pokemon endpoint for a variety of a species, e.g. `charizard-gmax`.
+    #[tracing::instrument(name = "Get pokemon variety", skip(self))]
+    pub async fn get_pokemon(&self, variety: &str) -> Result<PokemonResponse, ApiError> {
+        self.get_named(Self::POKEMON, variety).await
+    }
+
+    /// Makes a call to the Pokemon Form endpoint, e.g. for `unown-a`.
+    #[tracing::instrument(name = "Get pokemon form", skip(self))]
+    pub async fn get_pokemon_form(&self, form: &str) -> Result<PokemonFormResponse, ApiError> {
+        self.get_named(Self::POKEMON_FORM, form).await
+    }
+
+    /// Makes a call to the Move endpoint, e.g. for `thunderbolt`.
+    #[tracing::instrument(name = "Get move", skip(self))]
+    pub async fn get_move(&self, name: &str) -> Result<MoveResponse, ApiError> {
+        self.get_named(Self::MOVE, name).await
+    }
+
+    /// Makes a call to the Ability endpoint, e.g. for `blaze`.
+    #[tracing::instrument(name = "Get ability", skip(self))]
+    pub async fn get_ability(&self, name: &str) -> Result<AbilityResponse, ApiError> {
+        self.get_named(Self::ABILITY, name).await
+    }
+
+    /// Makes a call to the Item endpoint, e.g. for `potion`.
+    #[tracing::instrument(name = "Get item", skip(self))]
+    pub async fn get_item(&self, name: &str) -> Result<ItemResponse, ApiError> {
+        self.get_named(Self::ITEM, name).await
+    }
+
+    /// Makes a call to the Berry endpoint, e.g. for `oran`.
+    ///
+    /// Berries have no flavor texts of their own, these belong to the item of the berry.
+    #[tracing::instrument(name = "Get berry", skip(self))]
+    pub async fn get_berry(&self, name: &str) -> Result<BerryResponse, ApiError> {
+        self.get_named(Self::BERRY, name).await
+    }
+
+    /// Lists the names of all species known to the PokéAPI.
+    #[tracing::instrument(name = "List pokemon species", skip(self))]
+    pub async fn list_species(&self) -> Result<Vec<String>, ApiError> {
+        if let Some(dataset) = &self.dataset {
+            return Ok(dataset.species_names().to_vec());
+        }
+        self.list(Self::SPECIES).await
+    }
+
+    /// Lists the names of all varieties known to the PokéAPI, e.g. `charizard-mega-x`.
+    #[tracing::instrument(name = "List pokemon varieties", skip(self))]
+    pub async fn list_pokemon(&self) -> Result<Vec<String>, ApiError> {
+        if let Some(dataset) = &self.dataset {
+            return Ok(dataset.variety_names().to_vec());
+        }
+        self.list(Self::POKEMON).await
+    }
+
+    /// Lists the names of the species introduced in a generation, e.g. `generation-i` or `1`.
+    #[tracing::instrument(name = "List pokemon species of generation", skip(self))]
+    pub async fn generation_species(&self, generation: &str) -> Result<Vec<String>, ApiError> {
+        let generation: SpeciesGroup = self.get_named(Self::GENERATION, generation).await?;
+        Ok(generation.species_names())
+    }
+
+    /// Lists the names of the species living in a habitat, e.g. `cave`.
+    #[tracing::instrument(name = "List pokemon species of habitat", skip(self))]
+    pub async fn habitat_species(&self, habitat: &str) -> Result<Vec<String>, ApiError> {
+        let habitat: SpeciesGroup = self.get_named(Self::HABITAT, habitat).await?;
+        Ok(habitat.species_names())
+    }
+
+    /// Makes a call to the Evolution Chain endpoint, e.g. with the ID linked by a species.
+    #[tracing::instrument(name = "Get evolution chain", skip(self))]
+    pub async fn get_evolution_chain(&self, id: u32) -> Result<EvolutionChainResponse, ApiError> {
+        self.get_named(Self::EVOLUTION_CHAIN, &id.to_string()).await
+    }
+
+    /// Get the names of all resources of the given endpoint in a single page.
+    async fn list(&self, endpoint: &str) -> Result<Vec<String>, ApiError> {
+        let mut url = self.base_url.join(endpoint)?;
+        url.query_pairs_mut().append_pair("limit", Self::LIST_LIMIT);
+        let index: NamedResourceList = self.get(url).await?;
+        Ok(index.results.into_iter().map(|r| r.name).collect())
+    }
+
+    /// Get a resource of the given endpoint by its name or ID.
+    async fn get_named<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        name: &str,
+    ) -> Result<T, ApiError> {
+        if let Some(dataset) = &self.dataset {
+            return dataset.get(endpoint, name);
+        }
         let url = self
             .base_url
-            .join(Self::SPECIES)
-            .and_then(|url| url.join(pokemon))?;
+            .join(endpoint)
+            .and_then(|url| url.join(name))?;
+        self.get(url).await
+    }
 
-        let resp = self.client.get(url).send().await?;
-        resp.error_for_status_ref()?;
-        Ok(resp.json().await?)
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T, ApiError> {
+        metrics::observe_upstream(Self::CLIENT, async {
+            let resp = telemetry::propagate_context(self.client.get(url))
+                .send()
+                .await?;
+            resp.error_for_status_ref()?;
+            Ok(resp.json().await?)
+        })
+        .await
     }
 
-    /// Get the base URL of the PokéAPI.
+    /// Check whether the PokéAPI is reachable by querying its API root.
+    #[tracing::instrument(name = "Probe PokéAPI", skip(self))]
+    pub async fn probe(&self, timeout: Duration) -> Result<(), ApiError> {
+        if self.dataset.is_some() {
+            return Ok(());
+        }
+        metrics::observe_upstream(Self::CLIENT, async {
+            let url = self.base_url.join(Self::API_ROOT)?;
+            let resp = telemetry::propagate_context(self.client.get(url))
+                .timeout(timeout)
+                .send()
+                .await?;
+            resp.error_for_status()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the base URL of the PokéAPI, or the `file://` URL of the offline dataset.
     pub fn base_url(&self) -> &Url {
         &self.base_url
     }
@@ -52,22 +231,391 @@ impl PokeApi {
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct PokemonSpeciesResponse {
     // Most of the returned data is irrelevant for this service, thus it only contains the necessary bits.
+    id: u32,
+    name: String,
+    names: Vec<LocalizedName>,
+    genera: Vec<Genus>,
+    generation: NamedResource,
+    habitat: Option<NamedResource>,
+    color: NamedResource,
+    shape: Option<NamedResource>,
+    is_legendary: bool,
+    is_mythical: bool,
+    evolution_chain: Option<ApiResource>,
+    varieties: Vec<Variety>,
     flavor_text_entries: Vec<FlavourTextEntry>,
 }
 
+impl FlavorTexts for PokemonSpeciesResponse {
+    fn flavor_text_entries(&self) -> &[FlavourTextEntry] {
+        &self.flavor_text_entries
+    }
+}
+
 impl PokemonSpeciesResponse {
+    /// The National Pokédex number of the species.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The identifier of the species, e.g. `charizard`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The localized names of the species, e.g. `Charizard`.
+    pub fn names(&self) -> &[LocalizedName] {
+        &self.names
+    }
+
+    /// The localized genera of the species, e.g. `Flame Pokémon`.
+    pub fn genera(&self) -> &[Genus] {
+        &self.genera
+    }
+
+    /// The English name of the species, if there is one.
+    pub fn english_name(&self) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|n| n.language() == "en")
+            .map(LocalizedName::name)
+    }
+
+    /// The English genus of the species, if there is one.
+    pub fn english_genus(&self) -> Option<&str> {
+        self.genera
+            .iter()
+            .find(|g| g.language() == "en")
+            .map(Genus::genus)
+    }
+
+    /// The generation the species was introduced in, e.g. `generation-i`.
+    pub fn generation(&self) -> &str {
+        &self.generation.name
+    }
+
+    /// The habitat of the species, unknown for species introduced after the third generation.
+    pub fn habitat(&self) -> Option<&str> {
+        self.habitat.as_ref().map(NamedResource::name)
+    }
+
+    /// The Pokédex color of the species, e.g. `red`.
+    pub fn color(&self) -> &str {
+        &self.color.name
+    }
+
+    /// The Pokédex body shape of the species, e.g. `upright`.
+    pub fn shape(&self) -> Option<&str> {
+        self.shape.as_ref().map(NamedResource::name)
+    }
+
+    /// Whether the species is legendary.
+    pub fn is_legendary(&self) -> bool {
+        self.is_legendary
+    }
+
+    /// Whether the species is mythical.
+    pub fn is_mythical(&self) -> bool {
+        self.is_mythical
+    }
+
+    /// The varieties of the species, e.g. its mega evolutions.
+    pub fn varieties(&self) -> &[Variety] {
+        &self.varieties
+    }
+
+    /// The ID of the evolution chain the species belongs to.
+    ///
+    /// Only the ID is taken from the linked URL, which points to the public PokéAPI.
+    pub fn evolution_chain_id(&self) -> Option<u32> {
+        let url = &self.evolution_chain.as_ref()?.url;
+        url.trim_end_matches('/').rsplit('/').next()?.parse().ok()
+    }
+}
+
+/// A variety of a species, e.g. `charizard-mega-x`.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Variety {
+    is_default: bool,
+    pokemon: NamedResource,
+}
+
+impl Variety {
+    /// Whether this is the regular variety, named like the species itself.
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    pub fn name(&self) -> &str {
+        &self.pokemon.name
+    }
+}
+
+/// Response model for the Pokemon endpoint, i.e. a single variety of a species.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PokemonResponse {
+    id: u32,
+    name: String,
+    is_default: bool,
+    species: NamedResource,
+    forms: Vec<NamedResource>,
+}
+
+impl PokemonResponse {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether this is the regular variety of its species.
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    /// The name of the species of this variety.
+    pub fn species(&self) -> &str {
+        &self.species.name
+    }
+
+    /// The names of the forms of this variety, e.g. `charizard-mega-x`.
+    pub fn forms(&self) -> impl Iterator<Item = &str> {
+        self.forms.iter().map(NamedResource::name)
+    }
+}
+
+/// Response model for the Pokemon Form endpoint.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PokemonFormResponse {
+    id: u32,
+    name: String,
+    form_name: String,
+    is_mega: bool,
+    pokemon: NamedResource,
+}
+
+impl PokemonFormResponse {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the form without its species, e.g. `mega-x`, empty for regular forms.
+    pub fn form_name(&self) -> &str {
+        &self.form_name
+    }
+
+    pub fn is_mega(&self) -> bool {
+        self.is_mega
+    }
+
+    /// The name of the variety this form belongs to.
+    pub fn pokemon(&self) -> &str {
+        &self.pokemon.name
+    }
+}
+
+/// Response model for the Evolution Chain endpoint.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct EvolutionChainResponse {
+    id: u32,
+    chain: ChainLink,
+}
+
+impl EvolutionChainResponse {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// All stages of the chain, each one after the stage it evolves from.
+    pub fn stages(&self) -> Vec<EvolutionStage<'_>> {
+        let mut stages = Vec::new();
+        let mut pending = vec![(&self.chain, 1, None)];
+        while let Some((link, stage, evolves_from)) = pending.pop() {
+            let name = link.species.name();
+            stages.push(EvolutionStage {
+                name,
+                stage,
+                evolves_from,
+            });
+            // reversed to keep branches in the order of the PokéAPI
+            pending.extend(
+                link.evolves_to
+                    .iter()
+                    .rev()
+                    .map(|next| (next, stage + 1, Some(name))),
+            );
+        }
+        stages
+    }
+}
+
+/// A species in an evolution chain and the species it evolves into.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ChainLink {
+    species: NamedResource,
+    evolves_to: Vec<ChainLink>,
+}
+
+/// A flattened stage of an evolution chain.
+#[derive(Debug, PartialEq)]
+pub struct EvolutionStage<'a> {
+    /// The species name, e.g. `charmeleon`
+    pub name: &'a str,
+    /// Starting with 1 for the unevolved species
+    pub stage: u32,
+    pub evolves_from: Option<&'a str>,
+}
+
+/// An unnamed reference to another resource of the PokéAPI.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ApiResource {
+    url: String,
+}
+
+/// A reference to another resource of the PokéAPI, e.g. a species.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct NamedResource {
+    name: String,
+}
+
+impl NamedResource {
+    /// Get the identifier of the referenced resource.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The name of a resource in one language.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct LocalizedName {
+    name: String,
+    language: PokeApiLanguage,
+}
+
+impl LocalizedName {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the language code, e.g. `en` or `ja-Hrkt`.
+    pub fn language(&self) -> &str {
+        &self.language.name
+    }
+}
+
+/// The genus of a species in one language, e.g. `Flame Pokémon`.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Genus {
+    genus: String,
+    language: PokeApiLanguage,
+}
+
+impl Genus {
+    pub fn genus(&self) -> &str {
+        &self.genus
+    }
+
+    /// Get the language code, e.g. `en` or `ja-Hrkt`.
+    pub fn language(&self) -> &str {
+        &self.language.name
+    }
+}
+
+/// Response model for paginated resource lists like the species index.
+#[derive(Deserialize, Debug)]
+struct NamedResourceList {
+    results: Vec<NamedResource>,
+}
+
+/// Response model for resources grouping species, like generations and habitats.
+#[derive(Deserialize, Debug)]
+struct SpeciesGroup {
+    pokemon_species: Vec<NamedResource>,
+}
+
+impl SpeciesGroup {
+    fn species_names(self) -> Vec<String> {
+        self.pokemon_species.into_iter().map(|r| r.name).collect()
+    }
+}
+
+/// Resources with flavor texts, e.g. species, moves, abilities and items.
+pub trait FlavorTexts {
+    fn flavor_text_entries(&self) -> &[FlavourTextEntry];
+
     /// Convenience method for the Shakespeare translator to obtain an English flavor text.
-    pub fn english_flavor_text_entries(&self) -> impl Iterator<Item = &FlavourTextEntry> {
-        self.flavor_text_entries
+    fn english_flavor_text_entries(&self) -> impl Iterator<Item = &FlavourTextEntry> {
+        self.flavor_text_entries()
             .iter()
             .filter(|e| matches!(&*e.language.name, "en"))
     }
 }
 
+/// Response model for the endpoints of resources that are described by flavor texts alone, e.g.
+/// moves, abilities and items.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct FlavorTextResource {
+    id: u32,
+    name: String,
+    flavor_text_entries: Vec<FlavourTextEntry>,
+}
+
+impl FlavorTextResource {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl FlavorTexts for FlavorTextResource {
+    fn flavor_text_entries(&self) -> &[FlavourTextEntry] {
+        &self.flavor_text_entries
+    }
+}
+
+/// Response model for the Move endpoint.
+pub type MoveResponse = FlavorTextResource;
+
+/// Response model for the Ability endpoint.
+pub type AbilityResponse = FlavorTextResource;
+
+/// Response model for the Item endpoint.
+pub type ItemResponse = FlavorTextResource;
+
+/// Response model for the Berry endpoint.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct BerryResponse {
+    id: u32,
+    name: String,
+    item: NamedResource,
+}
+
+impl BerryResponse {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the name of the item of the berry, e.g. `oran-berry`.
+    pub fn item(&self) -> &str {
+        self.item.name()
+    }
+}
+
+/// A flavor text of any resource.
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct FlavourTextEntry {
+    // items call it `text`
+    #[serde(alias = "text")]
     flavor_text: String,
     language: PokeApiLanguage,
+    // species texts belong to a game version, all others to a version group
+    #[serde(alias = "version_group")]
+    version: PokeApiVersion,
 }
 
 impl FlavourTextEntry {
@@ -75,6 +623,12 @@ impl FlavourTextEntry {
     pub fn flavor_text(&self) -> &str {
         &self.flavor_text
     }
+
+    /// Get the name of the game version or version group the flavour text appeared in, e.g. `red`
+    /// or `gold-silver`.
+    pub fn version(&self) -> &str {
+        &self.version.name
+    }
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -82,6 +636,11 @@ pub struct PokeApiLanguage {
     name: String,
 }
 
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PokeApiVersion {
+    name: String,
+}
+
 #[cfg(test)]
 mod test {
     use wiremock::matchers::{method, path};
@@ -89,10 +648,22 @@ mod test {
 
     use crate::config::PokeApiUrl;
 
-    use super::{PokeApi, PokemonSpeciesResponse};
+    use super::{
+        AbilityResponse, BerryResponse, EvolutionChainResponse, FlavorTexts, ItemResponse,
+        MoveResponse, PokeApi, PokemonFormResponse, PokemonResponse, PokemonSpeciesResponse,
+    };
 
-    static CHARIZARD_RESPONSE: &'static [u8] = include_bytes!("../../testdata/charizard.json");
-    static PIKACHU_RESPONSE: &'static [u8] = include_bytes!("../../testdata/pikachu.json");
+    static CHARIZARD_RESPONSE: &[u8] = include_bytes!("../../testdata/charizard.json");
+    static MEGA_X_RESPONSE: &[u8] = include_bytes!("../../testdata/charizard_mega_x.json");
+    static MEGA_X_FORM_RESPONSE: &[u8] =
+        include_bytes!("../../testdata/charizard_mega_x_form.json");
+    static THUNDERBOLT_RESPONSE: &[u8] = include_bytes!("../../testdata/thunderbolt.json");
+    static BLAZE_RESPONSE: &[u8] = include_bytes!("../../testdata/blaze.json");
+    static ORAN_RESPONSE: &[u8] = include_bytes!("../../testdata/oran.json");
+    static ORAN_BERRY_RESPONSE: &[u8] = include_bytes!("../../testdata/oran_berry.json");
+    static EEVEE_CHAIN_RESPONSE: &[u8] =
+        include_bytes!("../../testdata/eevee_evolution_chain.json");
+    static PIKACHU_RESPONSE: &[u8] = include_bytes!("../../testdata/pikachu.json");
 
     #[tokio::test]
     async fn test_charizard() {
@@ -145,4 +716,139 @@ mod test {
             .english_flavor_text_entries()
             .all(|e| matches!(&*e.language.name, "en")));
     }
+
+    #[test]
+    fn test_metadata() {
+        let resp: PokemonSpeciesResponse = serde_json::from_slice(CHARIZARD_RESPONSE).unwrap();
+        assert_eq!(resp.id(), 6);
+        assert_eq!(resp.name(), "charizard");
+        assert_eq!(resp.english_name(), Some("Charizard"));
+        assert_eq!(resp.english_genus(), Some("Flame Pokémon"));
+        assert_eq!(resp.generation(), "generation-i");
+        assert_eq!(resp.habitat(), Some("mountain"));
+        assert_eq!(resp.color(), "red");
+        assert_eq!(resp.shape(), Some("upright"));
+        assert!(!resp.is_legendary());
+        assert!(!resp.is_mythical());
+        assert_eq!(resp.evolution_chain_id(), Some(2));
+    }
+
+    #[test]
+    fn test_varieties() {
+        let species: PokemonSpeciesResponse = serde_json::from_slice(CHARIZARD_RESPONSE).unwrap();
+        let varieties = species
+            .varieties()
+            .iter()
+            .map(|v| (v.name(), v.is_default()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            varieties,
+            [
+                ("charizard", true),
+                ("charizard-mega-x", false),
+                ("charizard-mega-y", false),
+                ("charizard-gmax", false),
+            ]
+        );
+
+        let variety: PokemonResponse = serde_json::from_slice(MEGA_X_RESPONSE).unwrap();
+        assert_eq!(variety.species(), "charizard");
+        assert!(!variety.is_default());
+        assert_eq!(variety.forms().collect::<Vec<_>>(), ["charizard-mega-x"]);
+
+        let form: PokemonFormResponse = serde_json::from_slice(MEGA_X_FORM_RESPONSE).unwrap();
+        assert_eq!(form.form_name(), "mega-x");
+        assert!(form.is_mega());
+        assert_eq!(form.pokemon(), "charizard-mega-x");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_species() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/{}charizard", PokeApi::SPECIES)))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(CHARIZARD_RESPONSE, "application/json"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/{}charizard-mega-x", PokeApi::POKEMON)))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(MEGA_X_RESPONSE, "application/json"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let api = PokeApi::new(PokeApiUrl(mock_server.uri().parse().unwrap()));
+        let species = api.resolve_species("charizard-mega-x").await.unwrap();
+        assert_eq!(species.name(), "charizard");
+
+        let err = api.resolve_species("missingno").await.unwrap_err();
+        assert!(err.is_not_found());
+    }
+
+    #[test]
+    fn test_move_and_ability() {
+        let thunderbolt: MoveResponse = serde_json::from_slice(THUNDERBOLT_RESPONSE).unwrap();
+        let versions = thunderbolt
+            .english_flavor_text_entries()
+            .map(|e| e.version())
+            .collect::<Vec<_>>();
+        assert_eq!(versions, ["gold-silver", "x-y"]);
+
+        let blaze: AbilityResponse = serde_json::from_slice(BLAZE_RESPONSE).unwrap();
+        let texts = blaze
+            .english_flavor_text_entries()
+            .map(|e| e.flavor_text())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["Powers up Fire-type moves in a pinch."]);
+    }
+
+    #[test]
+    fn test_item_and_berry() {
+        let oran: BerryResponse = serde_json::from_slice(ORAN_RESPONSE).unwrap();
+        assert_eq!(oran.item(), "oran-berry");
+
+        let oran_berry: ItemResponse = serde_json::from_slice(ORAN_BERRY_RESPONSE).unwrap();
+        let entries = oran_berry
+            .english_flavor_text_entries()
+            .map(|e| (e.version(), e.flavor_text()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                ("ruby-sapphire", "Restores 10 HP."),
+                (
+                    "x-y",
+                    "If held by a Pokémon, it heals the user by just 10 HP."
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_evolution_stages() {
+        let resp: EvolutionChainResponse = serde_json::from_slice(EEVEE_CHAIN_RESPONSE).unwrap();
+        let stages = resp
+            .stages()
+            .into_iter()
+            .map(|stage| (stage.name, stage.stage, stage.evolves_from))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            [
+                ("eevee", 1, None),
+                ("vaporeon", 2, Some("eevee")),
+                ("jolteon", 2, Some("eevee")),
+                ("flareon", 2, Some("eevee")),
+            ]
+        );
+    }
 }
@@ -1,48 +1,141 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::header::RETRY_AFTER;
 use reqwest::{StatusCode, Url};
 use serde::{Deserialize, Serialize};
 
 use crate::api_clients::ApiError;
 use crate::config::TranslateApiUrl;
+use crate::metrics;
+use crate::telemetry;
 
 /// API Client for Fun Translations' Shakespear translator.
 #[derive(Clone, Debug)]
 pub struct TranslationApi {
     client: reqwest::Client,
     base_url: Url,
+    // Shared between clones so that every handler sees the same quota state.
+    quota: Arc<Mutex<QuotaState>>,
+}
+
+/// The translation quota as observed from previous translation calls.
+#[derive(Debug, Default)]
+struct QuotaState {
+    exhausted_until: Option<Instant>,
+    /// The reported remaining quota, only valid until the current window has passed
+    remaining: Option<(u64, Instant)>,
 }
 
 impl TranslationApi {
+    const CLIENT: &'static str = "TranslationApi";
     const SHAKESPEARE_TRANSLATOR: &'static str = "translate/shakespeare.json";
+    const RATE_LIMIT_REMAINING: &'static str = "X-RateLimit-Remaining";
+    /// The free tier resets hourly, assume the worst if no `Retry-After` is sent.
+    const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+    /// A reported remaining quota is stale once the hourly window has passed.
+    const QUOTA_WINDOW: Duration = Duration::from_secs(60 * 60);
 
     /// Construct a new API client sending requests with the given base URL.
     pub fn new(base_url: TranslateApiUrl) -> Self {
         Self {
             client: reqwest::Client::new(),
             base_url: base_url.0,
+            quota: Arc::new(Mutex::new(QuotaState::default())),
         }
     }
 
     /// Translate the input string to Shakespearean English.
     #[tracing::instrument(name = "Get translation description", skip(self))]
     pub async fn translate(&self, text: &str) -> Result<String, ApiError> {
-        let url = self.base_url.join(Self::SHAKESPEARE_TRANSLATOR)?;
-        let resp = self
-            .client
-            .post(url)
-            .json(&TranslationRequest::from(text))
-            .send()
-            .await?;
-        let status = resp.status();
-        resp.error_for_status_ref().map_err(|e| {
-            if status == StatusCode::TOO_MANY_REQUESTS {
-                ApiError::RateLimit(e)
-            } else {
-                ApiError::Reqwest(e)
+        metrics::observe_upstream(Self::CLIENT, async {
+            let url = self.base_url.join(Self::SHAKESPEARE_TRANSLATOR)?;
+            let resp = telemetry::propagate_context(self.client.post(url))
+                .json(&TranslationRequest::from(text))
+                .send()
+                .await?;
+            let status = resp.status();
+            if let Some(remaining) = header_value(&resp, Self::RATE_LIMIT_REMAINING) {
+                self.set_remaining(remaining);
             }
-        })?;
+            resp.error_for_status_ref().map_err(|e| {
+                if status == StatusCode::TOO_MANY_REQUESTS {
+                    self.mark_exhausted(
+                        header_value(&resp, RETRY_AFTER.as_str())
+                            .map(Duration::from_secs)
+                            .unwrap_or(Self::DEFAULT_RETRY_AFTER),
+                    );
+                    ApiError::RateLimit(e)
+                } else {
+                    ApiError::Reqwest(e)
+                }
+            })?;
 
-        let parsed_resp: TranslationResponse = resp.json().await?;
-        Ok(parsed_resp.contents.translated)
+            let parsed_resp: TranslationResponse = resp.json().await?;
+            Ok(parsed_resp.contents.translated)
+        })
+        .await
+    }
+
+    /// Check whether the API is reachable and the translation quota is not exhausted.
+    ///
+    /// Probing does not consume any quota, the quota state is derived from previous
+    /// rate-limited translation calls.
+    #[tracing::instrument(name = "Probe translation API", skip(self))]
+    pub async fn probe(&self, timeout: Duration) -> Result<(), ApiError> {
+        if let Some(remaining) = self.quota_exhausted_for() {
+            return Err(ApiError::QuotaExhausted(remaining));
+        }
+        metrics::observe_upstream(Self::CLIENT, async {
+            // Any response that isn't a server error tells us the API is up.
+            let resp = telemetry::propagate_context(self.client.get(self.base_url.clone()))
+                .timeout(timeout)
+                .send()
+                .await?;
+            if resp.status().is_server_error() {
+                resp.error_for_status()?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the remaining time until the translation quota resets, if it is currently exhausted.
+    pub fn quota_exhausted_for(&self) -> Option<Duration> {
+        let mut quota = self.quota.lock().unwrap();
+        match quota.exhausted_until {
+            Some(until) if until > Instant::now() => Some(until - Instant::now()),
+            Some(_) => {
+                quota.exhausted_until = None;
+                quota.remaining = None;
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Get the remaining translation quota, if the API reported it within the current window.
+    ///
+    /// An exhausted quota is reported as 0 until it resets.
+    pub fn quota_remaining(&self) -> Option<u64> {
+        // clears the quota state once an exhausted quota has reset
+        self.quota_exhausted_for();
+        match self.quota.lock().unwrap().remaining {
+            Some((remaining, valid_until)) if valid_until > Instant::now() => Some(remaining),
+            _ => None,
+        }
+    }
+
+    fn set_remaining(&self, remaining: u64) {
+        self.quota.lock().unwrap().remaining =
+            Some((remaining, Instant::now() + Self::QUOTA_WINDOW));
+    }
+
+    fn mark_exhausted(&self, retry_after: Duration) {
+        let mut quota = self.quota.lock().unwrap();
+        let until = Instant::now() + retry_after;
+        quota.exhausted_until = Some(until);
+        quota.remaining = Some((0, until));
     }
 
     /// Get the base URL of the API.
@@ -51,6 +144,11 @@ impl TranslationApi {
     }
 }
 
+/// Parse a numeric header, e.g. the delay-seconds form of `Retry-After`.
+fn header_value(resp: &reqwest::Response, name: &str) -> Option<u64> {
+    resp.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
 #[derive(Serialize, Debug, PartialEq)]
 pub struct TranslationRequest<'a> {
     text: &'a str,
@@ -69,7 +167,7 @@ pub struct TranslationResponse {
 
 impl TranslationResponse {
     pub fn translation(&self) -> &str {
-        &*self.contents.translated
+        &self.contents.translated
     }
 }
 
@@ -80,6 +178,8 @@ pub struct TranslationContents {
 
 #[cfg(test)]
 mod test {
+    use std::time::{Duration, Instant};
+
     use wiremock::matchers::{body_json, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
@@ -88,7 +188,7 @@ mod test {
 
     use super::{TranslationApi, TranslationRequest};
 
-    static TRANSLATED_RESPONSE: &'static [u8] = include_bytes!("../../testdata/shakespeare.json");
+    static TRANSLATED_RESPONSE: &[u8] = include_bytes!("../../testdata/shakespeare.json");
 
     #[tokio::test]
     async fn test_shakespeare_api() {
@@ -100,7 +200,9 @@ mod test {
                 text: "You gave Mr. Tim a hearty meal, but unfortunately what he ate made him die.",
             }))
             .respond_with(
-                ResponseTemplate::new(200).set_body_raw(TRANSLATED_RESPONSE, "application/json"),
+                ResponseTemplate::new(200)
+                    .set_body_raw(TRANSLATED_RESPONSE, "application/json")
+                    .insert_header("X-RateLimit-Remaining", "4"),
             )
             .expect(1)
             .mount(&mock_server)
@@ -108,6 +210,7 @@ mod test {
 
         let addr = mock_server.uri();
         let api = TranslationApi::new(TranslateApiUrl(addr.parse().unwrap()));
+        assert_eq!(api.quota_remaining(), None);
 
         let resp = api
             .translate(
@@ -116,6 +219,11 @@ mod test {
             .await
             .unwrap();
         assert_eq!(resp, "Thee did giveth mr. Tim a hearty meal,  but unfortunately what he did doth englut did maketh him kicketh the bucket.");
+        assert_eq!(api.quota_remaining(), Some(4));
+
+        // the reported quota is stale once the window has passed
+        api.quota.lock().unwrap().remaining = Some((4, Instant::now()));
+        assert_eq!(api.quota_remaining(), None);
     }
 
     #[tokio::test]
@@ -139,6 +247,42 @@ mod test {
             .await
             .expect_err("The API call should have returned an error");
         assert!(matches!(resp, ApiError::RateLimit(_)));
+
+        // the exhausted quota is remembered for the probe
+        assert!(api.quota_exhausted_for().is_some());
+        assert_eq!(api.quota_remaining(), Some(0));
+        let probe = api
+            .probe(Duration::from_secs(1))
+            .await
+            .expect_err("The probe should report the exhausted quota");
+        assert!(matches!(probe, ApiError::QuotaExhausted(_)));
+    }
+
+    #[tokio::test]
+    async fn test_shakespeare_api_retry_after() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let addr = mock_server.uri();
+        let api = TranslationApi::new(TranslateApiUrl(addr.parse().unwrap()));
+
+        api.translate("Spits fire.")
+            .await
+            .expect_err("The API call should have returned an error");
+        assert!(api.quota_exhausted_for().is_none());
+        assert_eq!(api.quota_remaining(), None);
+        api.probe(Duration::from_secs(1))
+            .await
+            .expect("The API should be ready again");
     }
 
     #[tokio::test]
@@ -0,0 +1,362 @@
+//! API key authentication and per-key quotas.
+//!
+//! Consumers authenticate through the `X-Api-Key` header. Each key can be limited to a number
+//! of requests per hour and a budget of upstream translations per day, so that a single
+//! consumer can't exhaust the shared translation quota for everyone else.
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use futures::future::{ok, Ready};
+use serde::Deserialize;
+
+use crate::config::{ApiKeyConfig, AuthConfig};
+use crate::routes::{error_response, too_many_requests};
+
+/// Name of the header carrying the API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Paths served without a key, i.e. health checks and metrics scraping.
+const PUBLIC_PATHS: &[&str] = &["/healthz", "/readyz", "/metrics"];
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Possible errors while loading the API keys.
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Failed to load API keys from {path}: {source}")]
+    KeysFile {
+        path: PathBuf,
+        source: config::ConfigError,
+    },
+    #[error("The API key of {0} is configured more than once")]
+    DuplicateKey(String),
+    #[error("The name {0} is configured for more than one API key")]
+    DuplicateName(String),
+}
+
+#[derive(Deserialize)]
+struct KeysFile {
+    keys: Vec<ApiKeyConfig>,
+}
+
+/// All accepted API keys and the state of their quotas.
+pub struct ApiKeys(HashMap<String, ApiClient>);
+
+impl ApiKeys {
+    /// Collect the keys from the config and the optional keys file.
+    pub fn from_config(auth: &AuthConfig) -> Result<Self, AuthError> {
+        let mut configs = auth.keys.clone();
+        if let Some(path) = &auth.keys_file {
+            let load = || -> Result<KeysFile, config::ConfigError> {
+                let mut settings = config::Config::default();
+                settings.merge(config::File::from(path.as_path()))?;
+                settings.try_into()
+            };
+            let file = load().map_err(|source| AuthError::KeysFile {
+                path: path.clone(),
+                source,
+            })?;
+            configs.extend(file.keys);
+        }
+
+        let mut keys = HashMap::new();
+        // names identify the clients in rate limits, logs and metrics
+        let mut names = HashSet::new();
+        for config in configs {
+            if !names.insert(config.name.clone()) {
+                return Err(AuthError::DuplicateName(config.name));
+            }
+            let client = ApiClient::new(&config);
+            if keys.insert(config.key, client).is_some() {
+                return Err(AuthError::DuplicateKey(config.name));
+            }
+        }
+        Ok(ApiKeys(keys))
+    }
+
+    /// Authorize a request with the given key, counting it against the key's request quota.
+    fn authorize(&self, key: Option<&str>) -> Result<ApiClient, Rejection> {
+        let client = self
+            .0
+            .get(key.ok_or(Rejection::MissingKey)?)
+            .ok_or(Rejection::UnknownKey)?;
+        if client.0.disabled {
+            return Err(Rejection::Disabled(client.clone()));
+        }
+        client
+            .0
+            .requests
+            .as_ref()
+            .map_or(Ok(()), |quota| {
+                quota.lock().unwrap().acquire(Instant::now())
+            })
+            .map_err(|retry_after| Rejection::QuotaExceeded(client.clone(), retry_after))?;
+        Ok(client.clone())
+    }
+}
+
+/// The consumer of an authenticated request.
+///
+/// Handlers can access it through `web::ReqData<ApiClient>`, it is missing if authentication
+/// is disabled.
+#[derive(Clone)]
+pub struct ApiClient(Arc<ClientState>);
+
+struct ClientState {
+    name: String,
+    disabled: bool,
+    admin: bool,
+    requests: Option<Mutex<Window>>,
+    translations: Option<Mutex<Window>>,
+}
+
+impl ApiClient {
+    fn new(config: &ApiKeyConfig) -> Self {
+        ApiClient(Arc::new(ClientState {
+            name: config.name.clone(),
+            disabled: config.disabled,
+            admin: config.admin,
+            requests: config
+                .requests_per_hour
+                .map(|limit| Mutex::new(Window::new(limit, HOUR))),
+            translations: config
+                .translations_per_day
+                .map(|limit| Mutex::new(Window::new(limit, DAY))),
+        }))
+    }
+
+    /// The name of the consumer owning the key.
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Whether the key grants access to the `/admin` endpoints.
+    pub fn is_admin(&self) -> bool {
+        self.0.admin
+    }
+
+    /// Reserve one translation of the daily budget.
+    ///
+    /// Returns how long until the budget is replenished if it is exhausted.
+    pub fn acquire_translation(&self) -> Result<(), Duration> {
+        match &self.0.translations {
+            Some(budget) => budget.lock().unwrap().acquire(Instant::now()),
+            None => Ok(()),
+        }
+    }
+
+    /// Give back a reserved translation, e.g. if the upstream call failed.
+    pub fn release_translation(&self) {
+        if let Some(budget) = &self.0.translations {
+            budget.lock().unwrap().release();
+        }
+    }
+}
+
+/// Fixed window counter backing the request quotas and translation budgets.
+struct Window {
+    limit: u32,
+    period: Duration,
+    started: Instant,
+    used: u32,
+}
+
+impl Window {
+    fn new(limit: u32, period: Duration) -> Self {
+        Window {
+            limit,
+            period,
+            started: Instant::now(),
+            used: 0,
+        }
+    }
+
+    fn acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.duration_since(self.started);
+        if elapsed >= self.period {
+            self.started = now;
+            self.used = 0;
+        } else if self.used >= self.limit {
+            return Err(self.period - elapsed);
+        }
+        self.used += 1;
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        self.used = self.used.saturating_sub(1);
+    }
+}
+
+/// Reasons for rejecting a request.
+enum Rejection {
+    MissingKey,
+    UnknownKey,
+    Disabled(ApiClient),
+    QuotaExceeded(ApiClient, Duration),
+}
+
+impl Rejection {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            Rejection::MissingKey => error_response(StatusCode::UNAUTHORIZED, "Missing API key."),
+            Rejection::UnknownKey => {
+                tracing::info!("Rejected request with an unknown API key");
+                error_response(StatusCode::UNAUTHORIZED, "Invalid API key.")
+            }
+            Rejection::Disabled(client) => {
+                tracing::info!(
+                    "Rejected request with the disabled API key of {}",
+                    client.name()
+                );
+                error_response(StatusCode::FORBIDDEN, "API key is disabled.")
+            }
+            Rejection::QuotaExceeded(client, retry_after) => {
+                tracing::info!("Request quota of {} is exhausted", client.name());
+                too_many_requests("Request quota exceeded, try again later.", retry_after)
+            }
+        }
+    }
+}
+
+/// Middleware requiring a valid API key for all but the public endpoints.
+///
+/// Authenticated requests carry their `ApiClient` in the request extensions. Without keys,
+/// all requests are let through.
+pub struct ApiKeyAuth(Option<Arc<ApiKeys>>);
+
+impl ApiKeyAuth {
+    pub fn new(keys: Option<Arc<ApiKeys>>) -> Self {
+        ApiKeyAuth(keys)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ApiKeyAuthMiddleware {
+            service,
+            keys: self.0.clone(),
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct ApiKeyAuthMiddleware<S> {
+    service: S,
+    keys: Option<Arc<ApiKeys>>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let keys = match &self.keys {
+            Some(keys) if !PUBLIC_PATHS.contains(&req.path()) => keys,
+            _ => return Box::pin(self.service.call(req)),
+        };
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        match keys.authorize(key) {
+            Ok(client) => {
+                req.extensions_mut().insert(client);
+                Box::pin(self.service.call(req))
+            }
+            // the response is built when polled, within the request's span and ID scope
+            Err(rejection) => {
+                Box::pin(
+                    async move { Ok(req.into_response(rejection.into_response().into_body())) },
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::config::{ApiKeyConfig, AuthConfig};
+
+    use super::{ApiKeys, AuthError, Window};
+
+    fn key(name: &str, key: &str) -> ApiKeyConfig {
+        ApiKeyConfig {
+            name: name.into(),
+            key: key.into(),
+            disabled: false,
+            requests_per_hour: None,
+            translations_per_day: None,
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let auth = |keys| AuthConfig {
+            keys,
+            keys_file: None,
+        };
+        let keys = ApiKeys::from_config(&auth(vec![key("alice", "a"), key("bob", "b")]));
+        assert!(keys.is_ok());
+
+        let keys = ApiKeys::from_config(&auth(vec![key("alice", "a"), key("bob", "a")]));
+        assert!(matches!(keys, Err(AuthError::DuplicateKey(_))));
+
+        // clients with the same name would share their rate limit
+        let keys = ApiKeys::from_config(&auth(vec![key("alice", "a"), key("alice", "b")]));
+        assert!(matches!(keys, Err(AuthError::DuplicateName(name)) if name == "alice"));
+    }
+
+    #[test]
+    fn test_window() {
+        let start = Instant::now();
+        let mut window = Window::new(2, Duration::from_secs(60));
+        window.started = start;
+
+        assert!(window.acquire(start).is_ok());
+        assert!(window.acquire(start).is_ok());
+        let retry_after = window.acquire(start + Duration::from_secs(20));
+        assert_eq!(retry_after, Err(Duration::from_secs(40)));
+
+        // released units can be acquired again
+        window.release();
+        assert!(window.acquire(start + Duration::from_secs(20)).is_ok());
+
+        // the window is reset once the period has passed
+        assert!(window.acquire(start + Duration::from_secs(60)).is_ok());
+        assert_eq!(window.used, 1);
+    }
+}
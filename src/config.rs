@@ -1,3 +1,8 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use chrono_tz::Tz;
+use ipnet::IpNet;
 use serde::Deserialize;
 use url::Url;
 
@@ -7,10 +12,49 @@ pub struct Config {
     pub host: String,
     /// Port that our Server is listening on
     pub port: u16,
+    /// How long in-flight requests are drained on shutdown
+    #[serde(default = "Config::default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
     /// The base URL of the PokéApi
     pub poke_api_base_url: PokeApiUrl,
+    /// Optionally serve species from a local snapshot instead of the PokéApi
+    #[serde(default)]
+    pub dataset: Option<DatasetConfig>,
     /// The base URL of the Translator API
     pub translator_api_base_url: TranslateApiUrl,
+    /// Optional OpenTelemetry trace export, spans are only logged if unset
+    #[serde(default)]
+    pub otlp: Option<OtlpConfig>,
+    /// Log format, filters and destination
+    #[serde(default)]
+    pub log: LogConfig,
+    /// Optionally serve HTTPS instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Optionally require API keys, all requests are accepted if unset
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    /// Optionally limit the request rate of each client
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Optionally allow cross-origin requests from browser clients
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// Random Pokémon, e.g. how often a failed scan of the legendary species is retried
+    #[serde(default)]
+    pub random: RandomConfig,
+    /// Optionally serve a Pokémon of the day, pre-translated at midnight
+    #[serde(default)]
+    pub daily: Option<DailyConfig>,
+    /// Optionally reject unknown Pokémon without looking them up
+    #[serde(default)]
+    pub species_index: Option<SpeciesIndexConfig>,
+    /// Optionally pre-translate the descriptions of popular species at startup
+    #[serde(default)]
+    pub warm: Option<WarmConfig>,
+    /// Optionally keep the translation cache across restarts
+    #[serde(default)]
+    pub translation_cache: Option<TranslationCacheConfig>,
 }
 
 impl Config {
@@ -23,7 +67,46 @@ impl Config {
         settings.merge(config::File::from(cwd.join("config")).required(true))?;
         settings.merge(config::Environment::with_prefix("app").separator("__"))?;
 
-        Ok(settings.try_into()?)
+        let config: Self = settings.try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reject settings which deserialize fine but can't work, e.g. a zero interval.
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(tls) = &self.tls {
+            anyhow::ensure!(
+                tls.reload_interval_secs > 0,
+                "tls.reload_interval_secs must be greater than 0"
+            );
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            anyhow::ensure!(
+                rate_limit.burst > 0 && rate_limit.per_minute > 0,
+                "rate_limit.burst and rate_limit.per_minute must be greater than 0"
+            );
+        }
+        anyhow::ensure!(
+            self.random.legendary_retry_interval_secs > 0,
+            "random.legendary_retry_interval_secs must be greater than 0"
+        );
+        if let Some(daily) = &self.daily {
+            anyhow::ensure!(
+                daily.retry_interval_secs > 0,
+                "daily.retry_interval_secs must be greater than 0"
+            );
+        }
+        if let Some(species_index) = &self.species_index {
+            anyhow::ensure!(
+                species_index.refresh_interval_secs > 0 && species_index.retry_interval_secs > 0,
+                "species_index.refresh_interval_secs and species_index.retry_interval_secs must be greater than 0"
+            );
+        }
+        Ok(())
+    }
+
+    fn default_shutdown_timeout_secs() -> u64 {
+        30
     }
 }
 
@@ -34,3 +117,378 @@ pub struct PokeApiUrl(pub Url);
 #[derive(Clone, Debug, Deserialize)]
 #[serde(transparent)]
 pub struct TranslateApiUrl(pub Url);
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DatasetConfig {
+    /// Directory with species JSON documents or the PokéAPI CSV dump
+    pub path: PathBuf,
+    /// Format of the files in the directory
+    #[serde(default)]
+    pub format: DatasetFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetFormat {
+    /// Species documents as returned by `/api/v2/pokemon-species/{name}`
+    #[default]
+    Json,
+    /// The `data/v2/csv` directory of the PokéAPI repository
+    Csv,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OtlpConfig {
+    /// The OTLP/HTTP traces endpoint of the collector, e.g. `http://localhost:4318/v1/traces`
+    pub endpoint: Url,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Output format of the logs
+    pub format: LogFormat,
+    /// Default level for all modules, e.g. `info`
+    pub level: String,
+    /// Additional per-module filter directives, e.g. `pokespearify=debug`
+    pub directives: Vec<String>,
+    /// Optionally log to a rotating file instead of stdout
+    pub file: Option<LogFileConfig>,
+}
+
+impl LogConfig {
+    /// The `EnvFilter` directives combining the default level and per-module directives.
+    pub fn filter(&self) -> String {
+        std::iter::once(self.level.as_str())
+            .chain(self.directives.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Json,
+            level: "info".into(),
+            directives: Vec::new(),
+            file: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Bunyan-compatible JSON lines
+    Json,
+    /// Human-readable multi-line output for local development
+    Pretty,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogFileConfig {
+    /// Directory the log files are written to
+    pub directory: PathBuf,
+    /// File name prefix, the rotation date is appended
+    pub prefix: String,
+    /// How often a new file is started
+    #[serde(default)]
+    pub rotation: LogRotation,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain
+    pub cert_path: PathBuf,
+    /// PEM encoded PKCS#8 or RSA private key
+    pub key_path: PathBuf,
+    /// Minimum accepted TLS version
+    #[serde(default)]
+    pub min_version: TlsVersion,
+    /// PEM encoded CA certificates, client certificates are required if set
+    #[serde(default)]
+    pub client_ca_path: Option<PathBuf>,
+    /// How often the certificate and key files are checked for changes
+    #[serde(default = "TlsConfig::default_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+impl TlsConfig {
+    fn default_reload_interval_secs() -> u64 {
+        60
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AuthConfig {
+    /// Keys accepted in the `X-Api-Key` header
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+    /// YAML, JSON or TOML file with additional `keys`, e.g. mounted from a secret
+    #[serde(default)]
+    pub keys_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Unique name of the consumer, used in logs and to identify it in rate limits
+    pub name: String,
+    /// The secret key
+    pub key: String,
+    /// Reject all requests with this key without removing it
+    #[serde(default)]
+    pub disabled: bool,
+    /// Maximum number of requests per hour, unlimited if unset
+    #[serde(default)]
+    pub requests_per_hour: Option<u32>,
+    /// Maximum number of upstream translations per day, unlimited if unset
+    #[serde(default)]
+    pub translations_per_day: Option<u32>,
+    /// Grants access to the `/admin` endpoints
+    #[serde(default)]
+    pub admin: bool,
+}
+
+// keep the secret out of logs and panics
+impl fmt::Debug for ApiKeyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyConfig")
+            .field("name", &self.name)
+            .field("key", &"<redacted>")
+            .field("disabled", &self.disabled)
+            .field("requests_per_hour", &self.requests_per_hour)
+            .field("translations_per_day", &self.translations_per_day)
+            .field("admin", &self.admin)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RateLimitConfig {
+    /// Maximum number of requests a client can send in a burst
+    pub burst: u32,
+    /// Sustained number of requests per minute and client
+    pub per_minute: u32,
+    /// Proxies whose `X-Forwarded-For` header is trusted, e.g. `10.0.0.0/8`
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CorsConfig {
+    /// Origins allowed to call the service, e.g. `https://pokedex.example.com`, `*` allows all
+    pub allowed_origins: Vec<String>,
+    /// Allowed request methods
+    #[serde(default = "CorsConfig::default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Allowed request headers besides the CORS-safelisted ones, e.g. `x-api-key`
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache preflight responses
+    #[serde(default = "CorsConfig::default_max_age_secs")]
+    pub max_age_secs: usize,
+}
+
+impl CorsConfig {
+    fn default_allowed_methods() -> Vec<String> {
+        vec!["GET".into()]
+    }
+
+    fn default_max_age_secs() -> usize {
+        3600
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RandomConfig {
+    /// How long to wait before scanning the legendary species again after a failed scan
+    pub legendary_retry_interval_secs: u64,
+}
+
+impl Default for RandomConfig {
+    fn default() -> Self {
+        Self {
+            legendary_retry_interval_secs: 600,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DailyConfig {
+    /// IANA time zone whose midnight starts a new day, e.g. `Europe/Berlin`
+    #[serde(default = "DailyConfig::default_timezone")]
+    pub timezone: Tz,
+    /// How long to wait before retrying a failed preparation, e.g. on an exhausted quota
+    #[serde(default = "DailyConfig::default_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+}
+
+impl DailyConfig {
+    fn default_timezone() -> Tz {
+        Tz::UTC
+    }
+
+    fn default_retry_interval_secs() -> u64 {
+        300
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpeciesIndexConfig {
+    /// How often the index is fetched from the PokéApi again
+    #[serde(default = "SpeciesIndexConfig::default_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+    /// How long to wait before retrying a failed refresh
+    #[serde(default = "SpeciesIndexConfig::default_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+}
+
+impl SpeciesIndexConfig {
+    fn default_refresh_interval_secs() -> u64 {
+        86400
+    }
+
+    fn default_retry_interval_secs() -> u64 {
+        300
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WarmConfig {
+    /// File with one species name per line, e.g. `top100.txt`
+    pub species_file: PathBuf,
+    /// Minimum time between two translations, 30 minutes leave 3 of 5 hourly ones to requests
+    #[serde(default = "WarmConfig::default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl WarmConfig {
+    pub(crate) fn default_interval_secs() -> u64 {
+        1800
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TranslationCacheConfig {
+    /// JSON file the cache is loaded from at startup and saved to on shutdown
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Config, LogConfig, LogFormat, LogRotation};
+
+    fn parse_log_config(yaml: &str) -> LogConfig {
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from_str(yaml, config::FileFormat::Yaml))
+            .unwrap();
+        settings.try_into().unwrap()
+    }
+
+    /// Parse a config with the required settings and the given sections.
+    fn parse_config(sections: &str) -> Config {
+        let yaml = format!(
+            r#"
+host: 127.0.0.1
+port: 5000
+poke_api_base_url: https://pokeapi.co/
+translator_api_base_url: https://api.funtranslations.com/
+{}"#,
+            sections
+        );
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from_str(&yaml, config::FileFormat::Yaml))
+            .unwrap();
+        settings.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(parse_config("").validate().is_ok());
+
+        let tls = parse_config(
+            r#"
+tls:
+  cert_path: cert.pem
+  key_path: key.pem
+  reload_interval_secs: 0
+"#,
+        );
+        assert!(tls.validate().is_err());
+
+        let rate_limit = parse_config(
+            r#"
+rate_limit:
+  burst: 10
+  per_minute: 0
+"#,
+        );
+        assert!(rate_limit.validate().is_err());
+
+        for intervals in &[
+            "refresh_interval_secs: 0\n  retry_interval_secs: 300",
+            "refresh_interval_secs: 86400\n  retry_interval_secs: 0",
+        ] {
+            let species_index = parse_config(&format!("species_index:\n  {}\n", intervals));
+            assert!(species_index.validate().is_err());
+        }
+        let species_index = parse_config("species_index: {}\n");
+        assert!(species_index.validate().is_ok());
+
+        let daily = parse_config("daily:\n  retry_interval_secs: 0\n");
+        assert!(daily.validate().is_err());
+
+        let random = parse_config("random:\n  legendary_retry_interval_secs: 0\n");
+        assert!(random.validate().is_err());
+    }
+
+    #[test]
+    fn test_log_config() {
+        let log = parse_log_config(
+            r#"
+format: pretty
+level: warn
+directives:
+  - pokespearify=debug
+  - actix_web=info
+file:
+  directory: /var/log/pokespeare
+  prefix: pokespeare.log
+"#,
+        );
+        assert_eq!(log.format, LogFormat::Pretty);
+        assert_eq!(log.filter(), "warn,pokespearify=debug,actix_web=info");
+        assert_eq!(log.file.unwrap().rotation, LogRotation::Daily);
+    }
+
+    #[test]
+    fn test_log_config_defaults() {
+        let log = parse_log_config("level: debug");
+        assert_eq!(log.format, LogFormat::Json);
+        assert_eq!(log.filter(), "debug");
+        assert!(log.file.is_none());
+    }
+}
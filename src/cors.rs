@@ -0,0 +1,90 @@
+//! Cross-origin resource sharing for browser clients.
+use actix_cors::Cors;
+use actix_web::http::{HeaderName, Method, Uri};
+
+use crate::config::CorsConfig;
+use crate::request_id::REQUEST_ID_HEADER;
+
+/// Response headers browser clients may read besides the CORS-safelisted ones.
+const EXPOSED_HEADERS: &[&str] = &[
+    REQUEST_ID_HEADER,
+    "retry-after",
+    "ratelimit-limit",
+    "ratelimit-remaining",
+    "ratelimit-reset",
+];
+
+/// Possible errors in the CORS config.
+#[derive(Debug, thiserror::Error)]
+pub enum CorsConfigError {
+    #[error("Invalid CORS origin {0}")]
+    InvalidOrigin(String),
+    #[error("Invalid CORS method {0}")]
+    InvalidMethod(String),
+    #[error("Invalid CORS header {0}")]
+    InvalidHeader(String),
+}
+
+/// The validated CORS config.
+///
+/// The `Cors` middleware can't be shared between workers, so it is built from this policy
+/// for each of them.
+#[derive(Clone, Debug)]
+pub struct CorsPolicy {
+    /// `None` allows all origins
+    origins: Option<Vec<String>>,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+    max_age_secs: usize,
+}
+
+impl CorsPolicy {
+    pub fn new(config: &CorsConfig) -> Result<Self, CorsConfigError> {
+        let origins = if config.allowed_origins.iter().any(|origin| origin == "*") {
+            None
+        } else {
+            for origin in &config.allowed_origins {
+                origin
+                    .parse::<Uri>()
+                    .map_err(|_| CorsConfigError::InvalidOrigin(origin.clone()))?;
+            }
+            Some(config.allowed_origins.clone())
+        };
+        let methods = config
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|_| CorsConfigError::InvalidMethod(method.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        let headers = config
+            .allowed_headers
+            .iter()
+            .map(|header| {
+                HeaderName::from_bytes(header.as_bytes())
+                    .map_err(|_| CorsConfigError::InvalidHeader(header.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CorsPolicy {
+            origins,
+            methods,
+            headers,
+            max_age_secs: config.max_age_secs,
+        })
+    }
+
+    /// Build the middleware answering preflight requests and tagging responses.
+    pub fn middleware(&self) -> Cors {
+        let cors = match &self.origins {
+            Some(origins) => origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin)),
+            None => Cors::default().allow_any_origin(),
+        };
+        cors.allowed_methods(self.methods.clone())
+            .allowed_headers(self.headers.clone())
+            .expose_headers(EXPOSED_HEADERS.iter().copied())
+            .max_age(self.max_age_secs)
+    }
+}
@@ -1,19 +1,52 @@
 pub mod api_clients;
+pub mod auth;
 pub mod config;
+pub mod cors;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
 pub mod routes;
+pub mod species_index;
+pub mod species_roster;
 pub mod telemetry;
+pub mod tls;
+pub mod translation_cache;
+pub mod warming;
 
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
+use actix_cors::Cors;
 use actix_web::dev::Server;
+use actix_web::middleware::{Compress, Condition};
 use actix_web::{web, App, HttpServer};
 use tracing_actix_web::TracingLogger;
 
 use crate::api_clients::pokeapi::PokeApi;
 use crate::api_clients::shakespeare::TranslationApi;
-use crate::config::{PokeApiUrl, TranslateApiUrl};
-use crate::routes::healthz;
+use crate::auth::{ApiKeyAuth, ApiKeys};
+use crate::config::Config;
+use crate::cors::CorsPolicy;
+use crate::metrics::{metrics, RequestMetrics};
+use crate::rate_limit::{RateLimiter, RateLimits};
+use crate::request_id::RequestIdentifier;
+use crate::routes::admin::{self, delete_cache_entry, insert_cache_entry, list_cache, purge_cache};
+use crate::routes::batch::{self, batch};
+use crate::routes::daily::{self, daily, Daily};
+use crate::routes::evolutions::evolutions;
+use crate::routes::flavor_text::{
+    ability_description, berry_description, item_description, move_description,
+};
+use crate::routes::info::info;
 use crate::routes::pokemon::pokemon;
+use crate::routes::random::random;
+use crate::routes::readiness::{readyz, Readiness};
+use crate::routes::{healthz, not_found, path_error, query_error};
+use crate::species_index::SpeciesIndex;
+use crate::species_roster::SpeciesRoster;
+use crate::telemetry::TraceContext;
+use crate::translation_cache::TranslationCache;
 
 /// The Pokespeare Application.
 ///
@@ -21,6 +54,7 @@ use crate::routes::pokemon::pokemon;
 pub struct Application {
     server: Server,
     addr: SocketAddr,
+    translation_cache: web::Data<TranslationCache>,
 }
 
 impl Application {
@@ -28,25 +62,193 @@ impl Application {
     ///
     /// This method only constructs and starts the HTTP server, it then returns the Server handle.
     /// The `Application::run()` method can be used to await the server exit.
-    pub async fn new<A>(
-        addr: A,
-        poke_api_url: PokeApiUrl,
-        translate_api_url: TranslateApiUrl,
-    ) -> std::io::Result<Self>
-    where
-        A: ToSocketAddrs,
-    {
-        let poke_api = web::Data::new(PokeApi::new(poke_api_url));
-        let translate_api = web::Data::new(TranslationApi::new(translate_api_url));
+    pub async fn new(config: &Config) -> std::io::Result<Self> {
+        let poke_api = PokeApi::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let poke_api = web::Data::new(poke_api);
+        let translate_api =
+            web::Data::new(TranslationApi::new(config.translator_api_base_url.clone()));
+        let readiness = web::Data::new(Readiness::default());
+        let translation_cache = match &config.translation_cache {
+            Some(cache) => {
+                let cache = TranslationCache::open(cache.path.clone())
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                tracing::info!("Loaded {} cached translations", cache.len());
+                cache
+            }
+            None => TranslationCache::default(),
+        };
+        let translation_cache = web::Data::new(translation_cache);
+        let species_roster = web::Data::new(SpeciesRoster::new(&config.random));
+        let api_keys = config
+            .auth
+            .as_ref()
+            .map(ApiKeys::from_config)
+            .transpose()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .map(Arc::new);
+        let rate_limits = config
+            .rate_limit
+            .as_ref()
+            .map(|rate_limit| Arc::new(RateLimits::new(rate_limit)));
+        let cors = config
+            .cors
+            .as_ref()
+            .map(CorsPolicy::new)
+            .transpose()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let daily_pokemon = config
+            .daily
+            .as_ref()
+            .map(|daily| web::Data::new(Daily::new(daily)));
+        if let Some(daily_pokemon) = &daily_pokemon {
+            actix_web::rt::spawn(daily::refresh(
+                daily_pokemon.clone(),
+                poke_api.clone(),
+                translate_api.clone(),
+                translation_cache.clone(),
+            ));
+        }
+        if let Some(warm) = &config.warm {
+            let names = warming::read_species_file(&warm.species_file)?;
+            actix_web::rt::spawn(warming::warm(
+                names,
+                Duration::from_secs(warm.interval_secs),
+                poke_api.clone(),
+                translate_api.clone(),
+                translation_cache.clone(),
+            ));
+        }
+        let species_index = match &config.species_index {
+            Some(species_index) => {
+                let species_index = web::Data::new(SpeciesIndex::new(species_index));
+                // unknown names are only rejected once the index is loaded
+                actix_web::rt::spawn(species_index::refresh(
+                    species_index.clone(),
+                    poke_api.clone(),
+                ));
+                Some(species_index)
+            }
+            None => None,
+        };
+        // saved once the server has shut down
+        let shutdown_cache = translation_cache.clone();
         let srv = HttpServer::new(move || {
             App::new()
+                .wrap(ApiKeyAuth::new(api_keys.clone()))
+                // preflight requests are answered without an API key
+                .wrap(Condition::new(
+                    cors.is_some(),
+                    cors.as_ref()
+                        .map_or_else(Cors::default, CorsPolicy::middleware),
+                ))
+                .wrap(RequestMetrics)
+                .wrap(RequestIdentifier)
+                .wrap(TraceContext)
                 .wrap(TracingLogger)
+                .wrap(Compress::default())
                 .route("/healthz", web::get().to(healthz))
+                .route("/metrics", web::get().to(metrics))
                 .app_data(poke_api.clone())
                 .app_data(translate_api.clone())
-                .route("/pokemon/{pokemon_name}", web::get().to(pokemon))
+                .app_data(readiness.clone())
+                .app_data(translation_cache.clone())
+                .app_data(species_roster.clone())
+                // malformed requests are answered with problem details like all other errors
+                .app_data(web::QueryConfig::default().error_handler(query_error))
+                .app_data(web::PathConfig::default().error_handler(path_error))
+                .configure(|cfg| {
+                    if let Some(daily_pokemon) = &daily_pokemon {
+                        cfg.app_data(daily_pokemon.clone());
+                    }
+                    if let Some(species_index) = &species_index {
+                        cfg.app_data(species_index.clone());
+                    }
+                })
+                .route("/readyz", web::get().to(readyz))
+                // registered first, they would be shadowed by the lookup of a single Pokémon
+                .service(
+                    web::resource("/pokemon/batch")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .app_data(web::JsonConfig::default().error_handler(batch::json_error))
+                        .route(web::post().to(batch)),
+                )
+                .service(
+                    web::resource("/pokemon/random")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(random)),
+                )
+                .service(
+                    web::resource("/pokemon/daily")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(daily)),
+                )
+                .service(
+                    web::resource("/pokemon/{pokemon_name}")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(pokemon)),
+                )
+                .service(
+                    web::resource("/pokemon/{pokemon_name}/info")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(info)),
+                )
+                .service(
+                    web::resource("/pokemon/{pokemon_name}/evolutions")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(evolutions)),
+                )
+                .service(
+                    web::resource("/move/{move_name}")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(move_description)),
+                )
+                .service(
+                    web::resource("/ability/{ability_name}")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(ability_description)),
+                )
+                .service(
+                    web::resource("/item/{item_name}")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(item_description)),
+                )
+                .service(
+                    web::resource("/berry/{berry_name}")
+                        .wrap(RateLimiter::new(rate_limits.clone()))
+                        .route(web::get().to(berry_description)),
+                )
+                // admin endpoints don't reach the upstream APIs, they aren't rate limited
+                .service(
+                    web::resource("/admin/cache")
+                        .app_data(web::JsonConfig::default().error_handler(admin::json_error))
+                        .route(web::get().to(list_cache))
+                        .route(web::put().to(insert_cache_entry))
+                        .route(web::delete().to(purge_cache)),
+                )
+                .service(
+                    web::resource("/admin/cache/{entry_id}")
+                        .route(web::delete().to(delete_cache_entry)),
+                )
+                .default_service(web::route().to(not_found))
         })
-        .bind(addr)?;
+        // signals are handled by the caller through the `ShutdownHandle`
+        .disable_signals()
+        .shutdown_timeout(config.shutdown_timeout_secs);
+
+        let addr = (config.host.as_str(), config.port);
+        let srv = match &config.tls {
+            Some(tls) => {
+                let (server_config, resolver) = tls::server_config(tls)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                actix_web::rt::spawn(tls::watch_certificate(
+                    resolver,
+                    Duration::from_secs(tls.reload_interval_secs),
+                ));
+                srv.bind_rustls(addr, server_config)?
+            }
+            None => srv.bind(addr)?,
+        };
 
         let addrs = srv.addrs();
         let addr = addrs[0];
@@ -54,6 +256,7 @@ impl Application {
         Ok(Application {
             server: srv.run(),
             addr,
+            translation_cache: shutdown_cache,
         })
     }
 
@@ -62,8 +265,37 @@ impl Application {
         self.server.await
     }
 
+    /// Get a handle to shut the server down from another task.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            server: self.server.clone(),
+            translation_cache: self.translation_cache.clone(),
+        }
+    }
+
     /// Get the `SocketAddr`s this server is listening on.
     pub fn addr(&self) -> SocketAddr {
         self.addr
     }
 }
+
+/// Handle to gracefully stop a running `Application`.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    server: Server,
+    translation_cache: web::Data<TranslationCache>,
+}
+
+impl ShutdownHandle {
+    /// Stop accepting new connections and drain in-flight requests.
+    ///
+    /// Requests still running after the configured shutdown timeout are dropped. Resolves once
+    /// the server has stopped and the translation cache is saved, if it is kept in a file.
+    pub async fn shutdown(&self) {
+        self.server.stop(true).await;
+        match self.translation_cache.flush() {
+            Ok(()) => tracing::info!("Saved the translation cache"),
+            Err(e) => tracing::error!("Failed to save the translation cache: {:?}", e),
+        }
+    }
+}
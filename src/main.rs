@@ -1,23 +1,107 @@
+use std::path::PathBuf;
+
 use anyhow::Context;
 
 use pokespearify::config::Config;
-use pokespearify::telemetry::{get_subscriber, init_subscriber};
-use pokespearify::Application;
+use pokespearify::telemetry::{self, get_sink, get_subscriber, get_tracer, init_subscriber};
+use pokespearify::{warming, Application};
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
-    let subscriber = get_subscriber("pokespeare".into(), "info".into());
+    let config = Config::collect().context("Failed to collect config")?;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let command = Command::parse(&args)?;
+
+    let tracer = get_tracer("pokespeare".into(), config.otlp.as_ref())
+        .context("Failed to set up trace export")?;
+    let (sink, _log_guard) = get_sink(&config.log);
+    let subscriber = get_subscriber("pokespeare".into(), &config.log, sink, tracer);
     init_subscriber(subscriber);
 
-    let config = Config::collect().context("Failed to collect config")?;
+    let result = match command {
+        Command::Serve => serve(&config).await,
+        Command::Warm { species_file } => warming::warm_persisted(&config, &species_file).await,
+    };
+
+    // flush all spans which haven't been exported yet, even if the command failed, pending
+    // logs are flushed when the log guard is dropped
+    telemetry::shutdown();
+    result
+}
+
+/// What to run according to the command line.
+#[derive(Debug, PartialEq)]
+enum Command {
+    /// `pokespeare` serves requests until it is stopped.
+    Serve,
+    /// `pokespeare warm --species-file top100.txt` warms the persistent translation cache with
+    /// the descriptions of the listed species and exits, without serving any requests.
+    Warm { species_file: PathBuf },
+}
 
-    let app = Application::new(
-        (config.host, config.port),
-        config.poke_api_base_url,
-        config.translator_api_base_url,
-    )
-    .await?;
+impl Command {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        match args {
+            [] => Ok(Command::Serve),
+            [command, flag, species_file] if command == "warm" && flag == "--species-file" => {
+                Ok(Command::Warm {
+                    species_file: species_file.into(),
+                })
+            }
+            _ => anyhow::bail!("Usage: pokespeare [warm --species-file <file>]"),
+        }
+    }
+}
+
+/// Serve requests until the first shutdown signal.
+async fn serve(config: &Config) -> anyhow::Result<()> {
+    let app = Application::new(config).await?;
     tracing::info!("Service is listening under {}", app.addr());
-    app.run().await?;
-    Ok(())
+
+    let handle = app.shutdown_handle();
+    let signal = shutdown_signal().context("Failed to register signal handlers")?;
+    let shutdown_timeout_secs = config.shutdown_timeout_secs;
+    actix_web::rt::spawn(async move {
+        signal.await;
+        tracing::info!(
+            "Shutting down, draining in-flight requests for up to {}s",
+            shutdown_timeout_secs
+        );
+        handle.shutdown().await;
+    });
+    let stopped = app.run().await;
+    tracing::info!("Service stopped");
+    Ok(stopped?)
+}
+
+/// Resolves on the first SIGTERM or SIGINT.
+#[cfg(unix)]
+fn shutdown_signal() -> std::io::Result<impl std::future::Future<Output = ()>> {
+    use actix_web::rt::signal::{self, unix};
+    use futures::future::{self, Either};
+
+    let mut sigterm = unix::signal(unix::SignalKind::terminate())?;
+    Ok(async move {
+        let sigint = Box::pin(signal::ctrl_c());
+        let sigterm = Box::pin(sigterm.recv());
+        match future::select(sigint, sigterm).await {
+            Either::Left(_) => tracing::info!("Received SIGINT"),
+            Either::Right(_) => tracing::info!("Received SIGTERM"),
+        }
+    })
+}
+
+/// Resolves on the first Ctrl-C, there is no SIGTERM outside of unix.
+#[cfg(not(unix))]
+fn shutdown_signal() -> std::io::Result<impl std::future::Future<Output = ()>> {
+    Ok(async {
+        match actix_web::rt::signal::ctrl_c().await {
+            Ok(()) => tracing::info!("Received Ctrl-C"),
+            Err(e) => {
+                // keep serving, the service can still be stopped externally
+                tracing::warn!("Failed to listen for Ctrl-C: {}", e);
+                futures::future::pending::<()>().await
+            }
+        }
+    })
 }
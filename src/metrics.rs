@@ -0,0 +1,183 @@
+//! Prometheus metrics for the service.
+//!
+//! All metrics are registered in the default `prometheus` registry and exposed in the text
+//! format through the `/metrics` endpoint. Only the remaining translation quota is computed on
+//! scrape, so that it recovers as soon as the quota resets.
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::{web, Error, HttpResponse};
+use futures::future::{ok, Ready};
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    IntGauge, TextEncoder,
+};
+
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::api_clients::ApiError;
+
+lazy_static! {
+    static ref HTTP_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "http_requests_total",
+        "Number of HTTP requests handled, by route and status.",
+        &["method", "route", "status"]
+    )
+    .expect("Failed to register http_requests_total");
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Latency of HTTP requests, by route and status.",
+        &["method", "route", "status"]
+    )
+    .expect("Failed to register http_request_duration_seconds");
+    static ref UPSTREAM_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "upstream_requests_total",
+        "Number of calls to upstream APIs, by client.",
+        &["client"]
+    )
+    .expect("Failed to register upstream_requests_total");
+    static ref UPSTREAM_ERRORS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "upstream_errors_total",
+        "Number of failed calls to upstream APIs, by client and error kind.",
+        &["client", "kind"]
+    )
+    .expect("Failed to register upstream_errors_total");
+    static ref UPSTREAM_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "upstream_request_duration_seconds",
+        "Latency of calls to upstream APIs, by client.",
+        &["client"]
+    )
+    .expect("Failed to register upstream_request_duration_seconds");
+    static ref CACHE_LOOKUPS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "cache_lookups_total",
+        "Number of cache lookups, by cache and result (hit or miss).",
+        &["cache", "result"]
+    )
+    .expect("Failed to register cache_lookups_total");
+}
+
+/// Time an upstream API call and record its outcome for the given client.
+pub(crate) async fn observe_upstream<T>(
+    client: &str,
+    call: impl Future<Output = Result<T, ApiError>>,
+) -> Result<T, ApiError> {
+    UPSTREAM_REQUESTS_TOTAL.with_label_values(&[client]).inc();
+    let timer = UPSTREAM_REQUEST_DURATION
+        .with_label_values(&[client])
+        .start_timer();
+    let outcome = call.await;
+    timer.observe_duration();
+    if let Err(e) = &outcome {
+        UPSTREAM_ERRORS_TOTAL
+            .with_label_values(&[client, e.kind()])
+            .inc();
+    }
+    outcome
+}
+
+/// Record a cache lookup, hit ratios are derived from these counters.
+pub(crate) fn observe_cache_lookup(cache: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    CACHE_LOOKUPS_TOTAL
+        .with_label_values(&[cache, result])
+        .inc();
+}
+
+/// Handler for the metrics endpoint, returns all metrics in the Prometheus text format.
+///
+/// The remaining translation quota is omitted while it is unknown, e.g. before the first
+/// translation or once the reported quota is stale.
+pub async fn metrics(
+    translate_api: web::Data<TranslationApi>,
+) -> Result<HttpResponse, HttpResponse> {
+    let mut families = prometheus::gather();
+    if let Some(remaining) = translate_api.quota_remaining() {
+        let gauge = IntGauge::new(
+            "translation_quota_remaining",
+            "Remaining translation quota as last reported by the translation API.",
+        )
+        .expect("Failed to create translation_quota_remaining");
+        gauge.set(remaining as i64);
+        families.extend(gauge.collect());
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&families, &mut buffer).map_err(|e| {
+        tracing::error!("Failed to encode metrics: {}", e);
+        HttpResponse::InternalServerError().finish()
+    })?;
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, encoder.format_type()))
+        .body(buffer))
+}
+
+/// Middleware recording request counts and latencies per route and status.
+///
+/// Routes are labelled with their pattern, e.g. `/pokemon/{pokemon_name}`, to keep the
+/// label cardinality bounded.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestMetricsMiddleware { service })
+    }
+}
+
+#[doc(hidden)]
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let outcome = fut.await;
+            let status = match &outcome {
+                Ok(response) => response.response().status(),
+                Err(error) => error.as_response_error().status_code(),
+            };
+            let labels = [method.as_str(), route.as_str(), status.as_str()];
+            HTTP_REQUESTS_TOTAL.with_label_values(&labels).inc();
+            HTTP_REQUEST_DURATION
+                .with_label_values(&labels)
+                .observe(start.elapsed().as_secs_f64());
+            outcome
+        })
+    }
+}
@@ -0,0 +1,349 @@
+//! Inbound rate limiting through token buckets.
+//!
+//! Each client gets its own bucket, keyed by its API key or by its IP address. The IP is taken
+//! from `X-Forwarded-For` only if the request was forwarded by one of the trusted proxies.
+//! Responses carry the `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers.
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage};
+use futures::future::{ok, Ready};
+use ipnet::IpNet;
+
+use crate::auth::ApiClient;
+use crate::config::RateLimitConfig;
+use crate::routes::too_many_requests;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const RATELIMIT_LIMIT: &str = "ratelimit-limit";
+const RATELIMIT_REMAINING: &str = "ratelimit-remaining";
+const RATELIMIT_RESET: &str = "ratelimit-reset";
+
+/// How often idle clients are forgotten, pruning visits all buckets.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Identifies the client a bucket belongs to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ClientKey {
+    ApiKey(String),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The state of a client's bucket after taking a token.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    allowed: bool,
+    remaining: u32,
+    /// Until the bucket is full again
+    reset: Duration,
+    /// Until the next token is available
+    retry_after: Duration,
+}
+
+/// The buckets of all tracked clients.
+struct Buckets {
+    by_client: HashMap<ClientKey, Bucket>,
+    next_prune: Instant,
+}
+
+/// The token buckets of all clients.
+pub struct RateLimits {
+    capacity: f64,
+    per_sec: f64,
+    trusted_proxies: Vec<IpNet>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimits {
+            capacity: f64::from(config.burst),
+            per_sec: f64::from(config.per_minute) / 60.0,
+            trusted_proxies: config.trusted_proxies.clone(),
+            buckets: Mutex::new(Buckets {
+                by_client: HashMap::new(),
+                next_prune: Instant::now() + PRUNE_INTERVAL,
+            }),
+        }
+    }
+
+    fn acquire(&self, key: ClientKey, now: Instant) -> Outcome {
+        let mut buckets = self.buckets.lock().unwrap();
+        if now >= buckets.next_prune {
+            // full buckets are indistinguishable from new ones
+            buckets
+                .by_client
+                .retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+            buckets.next_prune = now + PRUNE_INTERVAL;
+        }
+        let bucket = buckets.by_client.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Outcome {
+            allowed,
+            remaining: bucket.tokens as u32,
+            reset: self.time_to(self.capacity - bucket.tokens),
+            retry_after: self.time_to(1.0 - bucket.tokens),
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_sec).min(self.capacity)
+    }
+
+    /// How long it takes to refill the given number of tokens.
+    fn time_to(&self, tokens: f64) -> Duration {
+        if tokens <= 0.0 || self.per_sec <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(tokens / self.per_sec)
+    }
+
+    /// Find the IP of the client, skipping all trusted proxies it was forwarded through.
+    fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.is_trusted(peer) {
+            return Some(peer);
+        }
+        // every proxy appends the address it received the request from
+        let mut client = peer;
+        for hop in forwarded_for
+            .into_iter()
+            .flat_map(|value| value.rsplit(','))
+        {
+            match hop.trim().parse() {
+                Ok(ip) => client = ip,
+                // anything left of a malformed entry can't be trusted
+                Err(_) => break,
+            }
+            if !self.is_trusted(client) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+}
+
+fn insert_headers(headers: &mut HeaderMap, limit: f64, outcome: &Outcome) {
+    let ceil_secs = |duration: Duration| duration.as_secs_f64().ceil() as u64;
+    for (name, value) in &[
+        (RATELIMIT_LIMIT, limit as u64),
+        (RATELIMIT_REMAINING, u64::from(outcome.remaining)),
+        (RATELIMIT_RESET, ceil_secs(outcome.reset)),
+    ] {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(*value));
+    }
+}
+
+/// Middleware limiting the request rate of each client.
+///
+/// It has to be registered *inside* of `ApiKeyAuth` to key the buckets by API key. Without
+/// limits, all requests are let through.
+pub struct RateLimiter(Option<Arc<RateLimits>>);
+
+impl RateLimiter {
+    pub fn new(limits: Option<Arc<RateLimits>>) -> Self {
+        RateLimiter(limits)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimiterMiddleware {
+            service,
+            limits: self.0.clone(),
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RateLimiterMiddleware<S> {
+    service: S,
+    limits: Option<Arc<RateLimits>>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let limits = match &self.limits {
+            Some(limits) => limits.clone(),
+            None => return Box::pin(self.service.call(req)),
+        };
+        let api_client = req
+            .extensions()
+            .get::<ApiClient>()
+            .map(|client| ClientKey::ApiKey(client.name().to_string()));
+        let key = api_client.or_else(|| {
+            let forwarded_for = req
+                .headers()
+                .get(X_FORWARDED_FOR)
+                .and_then(|value| value.to_str().ok());
+            limits
+                .client_ip(req.peer_addr().map(|addr| addr.ip()), forwarded_for)
+                .map(ClientKey::Ip)
+        });
+        let key = match key {
+            Some(key) => key,
+            // only happens for requests without a peer, i.e. in unit tests
+            None => return Box::pin(self.service.call(req)),
+        };
+
+        let outcome = limits.acquire(key.clone(), Instant::now());
+        if !outcome.allowed {
+            // the response is built when polled, within the request's span and ID scope
+            return Box::pin(async move {
+                tracing::info!("Rate limited {:?}", key);
+                let mut response =
+                    too_many_requests("Rate limit exceeded, slow down.", outcome.retry_after);
+                insert_headers(response.headers_mut(), limits.capacity, &outcome);
+                Ok(req.into_response(response.into_body()))
+            });
+        }
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut response = fut.await?;
+            insert_headers(response.headers_mut(), limits.capacity, &outcome);
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    use crate::config::RateLimitConfig;
+
+    use super::{ClientKey, RateLimits, PRUNE_INTERVAL};
+
+    fn limits(trusted_proxies: &[&str]) -> RateLimits {
+        RateLimits::new(&RateLimitConfig {
+            burst: 2,
+            per_minute: 60,
+            trusted_proxies: trusted_proxies.iter().map(|p| p.parse().unwrap()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limits = limits(&[]);
+        let client = ClientKey::Ip("192.0.2.1".parse().unwrap());
+        let start = Instant::now();
+
+        let outcome = limits.acquire(client.clone(), start);
+        assert!(outcome.allowed);
+        assert_eq!(outcome.remaining, 1);
+        assert_eq!(outcome.reset, Duration::from_secs(1));
+        assert!(limits.acquire(client.clone(), start).allowed);
+
+        let outcome = limits.acquire(client.clone(), start);
+        assert!(!outcome.allowed);
+        assert_eq!(outcome.remaining, 0);
+        assert_eq!(outcome.retry_after, Duration::from_secs(1));
+
+        // other clients have their own bucket
+        let other = ClientKey::ApiKey("alice".into());
+        assert!(limits.acquire(other, start).allowed);
+
+        // one token is refilled per second
+        assert!(
+            limits
+                .acquire(client, start + Duration::from_secs(1))
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let limits = limits(&[]);
+        let idle = ClientKey::Ip("192.0.2.1".parse().unwrap());
+        let active = ClientKey::Ip("192.0.2.2".parse().unwrap());
+        let start = Instant::now();
+        limits.acquire(idle.clone(), start);
+        limits.acquire(active.clone(), start);
+        assert_eq!(limits.buckets.lock().unwrap().by_client.len(), 2);
+
+        // only clients whose bucket has been refilled completely are forgotten
+        let later = start + PRUNE_INTERVAL;
+        limits.acquire(active.clone(), later);
+        let buckets = limits.buckets.lock().unwrap();
+        assert_eq!(buckets.by_client.len(), 1);
+        assert!(buckets.by_client.contains_key(&active));
+        assert!(!buckets.by_client.contains_key(&idle));
+        assert_eq!(buckets.next_prune, later + PRUNE_INTERVAL);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let limits = limits(&["10.0.0.0/8"]);
+        let ip = |ip: &str| Some(ip.parse::<IpAddr>().unwrap());
+
+        // headers of untrusted peers are ignored
+        let forwarded = Some("198.51.100.7");
+        assert_eq!(
+            limits.client_ip(ip("192.0.2.1"), forwarded),
+            ip("192.0.2.1")
+        );
+
+        // trusted proxies are skipped from the right
+        let forwarded = Some("203.0.113.9, 198.51.100.7, 10.0.0.2");
+        assert_eq!(
+            limits.client_ip(ip("10.0.0.1"), forwarded),
+            ip("198.51.100.7")
+        );
+
+        // malformed entries end the chain
+        let forwarded = Some("198.51.100.7, unknown");
+        assert_eq!(limits.client_ip(ip("10.0.0.1"), forwarded), ip("10.0.0.1"));
+        assert_eq!(limits.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Correlation of requests through an `X-Request-Id`.
+//!
+//! The ID is taken from the incoming request or generated, recorded on the root span of
+//! `TracingLogger`, returned in the response headers and error bodies and forwarded to all
+//! upstream calls made while handling the request.
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage};
+use futures::future::{ok, Ready};
+use tracing::Span;
+use uuid::Uuid;
+
+/// Name of the header carrying the request ID.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// The ID correlating all logs, responses and upstream calls of a single request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Longer IDs from clients are replaced to keep our logs and upstream headers sane.
+    const MAX_LEN: usize = 128;
+
+    /// Use the ID provided by the client if it's well-formed or generate a new one.
+    fn from_header(header: Option<&HeaderValue>) -> Self {
+        header
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= Self::MAX_LEN)
+            .filter(|id| id.chars().all(|c| c.is_ascii_graphic()))
+            .map(|id| RequestId(id.to_string()))
+            .unwrap_or_else(|| RequestId(Uuid::new_v4().to_string()))
+    }
+
+    /// Get the ID of the request currently being handled, if any.
+    pub fn current() -> Option<RequestId> {
+        CURRENT.try_with(|id| id.clone()).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Middleware accepting or generating the `X-Request-Id` of each request.
+///
+/// It has to be registered *inside* of `TracingLogger`, i.e. wrapped before it, so that the
+/// ID can be recorded on its root span.
+pub struct RequestIdentifier;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdentifier
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdentifierMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdentifierMiddleware { service })
+    }
+}
+
+#[doc(hidden)]
+pub struct RequestIdentifierMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdentifierMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = RequestId::from_header(req.headers().get(REQUEST_ID_HEADER));
+        req.extensions_mut().insert(request_id.clone());
+        // extractors run right away while handlers only run when polling, both see the ID
+        let fut = CURRENT.sync_scope(request_id.clone(), || self.service.call(req));
+        let fut = CURRENT.scope(request_id.clone(), fut);
+        Box::pin(async move {
+            // polled within the root span of `TracingLogger`, replacing its generated ID
+            Span::current().record("request_id", &request_id.as_str());
+            // handler errors are already turned into responses at this point
+            let mut response = fut.await?;
+            if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::http::HeaderValue;
+
+    use super::RequestId;
+
+    #[test]
+    fn test_request_id_from_header() {
+        let id = RequestId::from_header(Some(&HeaderValue::from_static("support-1234")));
+        assert_eq!(id.as_str(), "support-1234");
+
+        // missing, empty, overly long or malformed IDs are replaced
+        for header in &[
+            None,
+            Some(HeaderValue::from_static("")),
+            Some(HeaderValue::from_str(&"a".repeat(129)).unwrap()),
+            Some(HeaderValue::from_static("with space")),
+        ] {
+            let id = RequestId::from_header(header.as_ref());
+            assert!(uuid::Uuid::parse_str(id.as_str()).is_ok());
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Inspection and maintenance of the translation cache by operators.
+//!
+//! Cached translations are identified by a short hash of their original text, since flavor
+//! texts contain line breaks and other characters that are awkward in paths. Every change is
+//! saved right away if the cache is kept in a file, so that it survives a crash.
+use std::collections::BTreeSet;
+
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::ApiClient;
+use crate::routes::error_response;
+use crate::translation_cache::{CachedTranslation, TranslationCache};
+
+/// Handler listing the species with cached descriptions and all cached translations with their
+/// age and hit count.
+#[tracing::instrument(name = "List cached translations", skip(translation_cache, client))]
+pub async fn list_cache(
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    require_admin(client.as_deref())?;
+    let entries = translation_cache.entries();
+    let species = entries
+        .iter()
+        .flat_map(|cached| cached.species.iter().cloned())
+        .collect::<BTreeSet<_>>();
+    Ok(HttpResponse::Ok().json(CacheListing {
+        species: species.into_iter().collect(),
+        entries: entries.iter().map(CacheEntry::from).collect(),
+    }))
+}
+
+/// Handler inserting a translation, e.g. to correct a bad one.
+///
+/// Inserted translations are pinned, they are neither evicted nor replaced by new translations.
+#[tracing::instrument(
+    name = "Insert cached translation",
+    skip(body, translation_cache, client)
+)]
+pub async fn insert_cache_entry(
+    body: web::Json<CacheInsertion>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    require_admin(client.as_deref())?;
+    let CacheInsertion { text, translation } = body.into_inner();
+    if text.is_empty() || translation.is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "Text and translation must not be empty.",
+        ));
+    }
+    tracing::info!("Caching a corrected translation of {}", entry_id(&text));
+    let cached = translation_cache.pin(&text, translation);
+    save(&translation_cache)?;
+    Ok(HttpResponse::Ok().json(CacheEntry::from(&cached)))
+}
+
+/// Handler removing a single cached translation by its ID.
+#[tracing::instrument(name = "Delete cached translation", skip(translation_cache, client))]
+pub async fn delete_cache_entry(
+    entry: web::Path<String>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    require_admin(client.as_deref())?;
+    let text = translation_cache
+        .entries()
+        .into_iter()
+        .find(|cached| entry_id(&cached.text) == *entry)
+        .map(|cached| cached.text);
+    match text {
+        Some(text) if translation_cache.remove(&text) => {
+            save(&translation_cache)?;
+            Ok(HttpResponse::NoContent().finish())
+        }
+        _ => Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Unknown cached translation.",
+        )),
+    }
+}
+
+/// Handler removing all cached translations.
+#[tracing::instrument(name = "Purge cached translations", skip(translation_cache, client))]
+pub async fn purge_cache(
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    require_admin(client.as_deref())?;
+    let purged = translation_cache.clear();
+    tracing::info!("Purged {} cached translations", purged);
+    save(&translation_cache)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Save the translation cache after a change, the change itself is kept in memory regardless.
+fn save(translation_cache: &TranslationCache) -> Result<(), HttpResponse> {
+    translation_cache.flush().map_err(|e| {
+        tracing::error!("Failed to save the translation cache: {:?}", e);
+        error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to save the translation cache.",
+        )
+    })
+}
+
+/// Only admin keys may access the admin endpoints, they are closed without authentication.
+fn require_admin(client: Option<&ApiClient>) -> Result<(), HttpResponse> {
+    match client {
+        Some(client) if client.is_admin() => Ok(()),
+        _ => Err(error_response(
+            StatusCode::FORBIDDEN,
+            "An admin API key is required.",
+        )),
+    }
+}
+
+/// Turn malformed insertions into problem responses.
+pub fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = error_response(
+        StatusCode::BAD_REQUEST,
+        &format!("Invalid cache entry: {}", err),
+    );
+    InternalError::from_response(err, response).into()
+}
+
+/// Short, stable ID of a cached translation.
+fn entry_id(text: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(text.as_bytes()));
+    digest[..16].to_string()
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheListing {
+    /// The species with at least one cached description
+    species: Vec<String>,
+    entries: Vec<CacheEntry>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheEntry {
+    id: String,
+    text: String,
+    translation: String,
+    age_secs: u64,
+    hits: u64,
+    pinned: bool,
+    species: Vec<String>,
+}
+
+impl From<&CachedTranslation> for CacheEntry {
+    fn from(cached: &CachedTranslation) -> Self {
+        CacheEntry {
+            id: entry_id(&cached.text),
+            text: cached.text.clone(),
+            translation: cached.translation.clone(),
+            age_secs: cached.age.as_secs(),
+            hits: cached.hits,
+            pinned: cached.pinned,
+            species: cached.species.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CacheInsertion {
+    text: String,
+    translation: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::entry_id;
+
+    #[test]
+    fn test_entry_id() {
+        let id = entry_id("Spits fire that\nis hot enough to\nmelt boulders.");
+        assert_eq!(id.len(), 16);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(
+            id,
+            entry_id("Spits fire that\nis hot enough to\nmelt boulders.")
+        );
+        assert_ne!(
+            id,
+            entry_id("Spits fire that is hot enough to melt boulders.")
+        );
+    }
+}
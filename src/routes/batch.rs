@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::auth::ApiClient;
+use crate::routes::error_response;
+use crate::routes::pokemon::{lookup_species, DescriptionQuery};
+use crate::routes::translation::{translate, Outcome};
+use crate::species_index::SpeciesIndex;
+use crate::translation_cache::TranslationCache;
+
+/// Upper bound of names in a single batch.
+const MAX_BATCH_SIZE: usize = 100;
+/// Number of species fetched from the PokéAPI concurrently.
+const CONCURRENT_FETCHES: usize = 8;
+
+/// Handler for the batch endpoint.
+///
+/// Returns the shakespeare-ified descriptions of many Pokémon at once, with an individual
+/// status for each of them. Names are looked up like for a single Pokémon, i.e. varieties are
+/// described by their species and the species index rejects unknown names.
+///
+/// # Implementation Detail
+///
+/// Each species is only fetched once and each flavor text is only translated once per batch.
+/// Translations stop as soon as the translation quota or the client's translation budget is
+/// exhausted, the remaining names are answered with a 429 status while all others are still
+/// returned.
+#[tracing::instrument(
+    name = "Return shakespeared Pokémon descriptions for a batch",
+    skip(batch, poke_api, translate_api, translation_cache, species_index, client),
+    fields(batch_size = batch.names.len())
+)]
+pub async fn batch(
+    batch: web::Json<BatchRequest>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    species_index: Option<web::Data<SpeciesIndex>>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    if batch.names.is_empty() || batch.names.len() > MAX_BATCH_SIZE {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("A batch has to contain 1 to {} names.", MAX_BATCH_SIZE),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let unique_names = batch
+        .names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| seen.insert(*name));
+    let species = stream::iter(unique_names)
+        .map(|name| {
+            let poke_api = &poke_api;
+            let species_index = species_index.as_ref().map(|index| index.get_ref());
+            async move { (name, lookup_species(name, poke_api, species_index).await) }
+        })
+        .buffer_unordered(CONCURRENT_FETCHES)
+        .collect::<HashMap<_, _>>()
+        .await;
+
+    let flavor_texts = species
+        .iter()
+        .map(|(name, species)| {
+            let flavor_text = species.as_ref().map_err(|e| *e).and_then(|species| {
+                batch
+                    .query
+                    .choose(species)
+                    .map(|entry| entry.flavor_text())
+                    .ok_or((StatusCode::NOT_FOUND, "No English description found."))
+            });
+            (*name, flavor_text)
+        })
+        .collect::<HashMap<_, _>>();
+
+    // translated one after another in request order, so that only the trailing names are
+    // answered with a rate limit
+    let mut translations = HashMap::new();
+    for name in &batch.names {
+        let flavor_text = match flavor_texts[name.as_str()] {
+            Ok(flavor_text) => flavor_text,
+            Err(_) => continue,
+        };
+        if translations.contains_key(flavor_text) {
+            continue;
+        }
+        let species = species[name.as_str()]
+            .as_ref()
+            .ok()
+            .map(|species| species.name());
+        let translation = translate(
+            flavor_text,
+            species,
+            &translate_api,
+            &translation_cache,
+            client.as_deref(),
+        )
+        .await
+        .map_err(Into::into);
+        translations.insert(flavor_text, translation);
+    }
+
+    let results = batch
+        .names
+        .iter()
+        .map(|name| {
+            let outcome = flavor_texts[name.as_str()].and_then(|text| translations[text].clone());
+            BatchItem::new(name.clone(), outcome)
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(BatchResponse { results }))
+}
+
+/// Turn malformed batch requests into problem responses.
+pub fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = error_response(
+        StatusCode::BAD_REQUEST,
+        &format!("Invalid batch request: {}", err),
+    );
+    InternalError::from_response(err, response).into()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BatchRequest {
+    names: Vec<String>,
+    /// The same selection applies to all names
+    #[serde(flatten)]
+    query: DescriptionQuery,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchResponse {
+    results: Vec<BatchItem>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchItem {
+    name: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BatchItem {
+    fn new(name: String, outcome: Outcome) -> Self {
+        match outcome {
+            Ok(description) => BatchItem {
+                name,
+                status: StatusCode::OK.as_u16(),
+                description: Some(description),
+                error: None,
+            },
+            Err((status, error)) => BatchItem {
+                name,
+                status: status.as_u16(),
+                description: None,
+                error: Some(error.to_string()),
+            },
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! HTTP caching of our own responses.
+//!
+//! Deterministic responses are tagged with a strong `ETag` and may be cached by clients and
+//! CDNs, randomized responses must not be cached at all.
+use std::time::Duration;
+
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, VARY};
+use actix_web::http::HeaderValue;
+use actix_web::{HttpRequest, HttpResponse};
+use mime::Mime;
+use sha2::{Digest, Sha256};
+
+use crate::auth::{ApiClient, API_KEY_HEADER};
+
+/// How long deterministic responses may be cached.
+pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Respond with a body which may be cached for `max_age`, honoring `If-None-Match`.
+pub fn cacheable(
+    req: &HttpRequest,
+    content_type: Mime,
+    body: Vec<u8>,
+    max_age: Duration,
+) -> HttpResponse {
+    let etag = format!("\"{:x}\"", Sha256::digest(&body));
+
+    let mut response = if is_fresh(req.headers().get(IF_NONE_MATCH), &etag) {
+        HttpResponse::NotModified().finish()
+    } else {
+        HttpResponse::Ok().content_type(content_type).body(body)
+    };
+    let headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(ETAG, etag);
+    }
+    headers.insert(
+        CACHE_CONTROL,
+        HeaderValue::from_str(&format!("public, max-age={}", max_age.as_secs())).unwrap(),
+    );
+    // the body is compressed depending on `Accept-Encoding`
+    headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    // shared caches must not serve responses to clients without access
+    if req.extensions().get::<ApiClient>().is_some() {
+        headers.append(VARY, HeaderValue::from_static(API_KEY_HEADER));
+    }
+    response
+}
+
+/// Respond with a body which must not be cached.
+pub fn uncacheable(content_type: Mime, body: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .content_type(content_type)
+        .body(body)
+}
+
+/// Whether the client's cached response, identified by `If-None-Match`, is still current.
+fn is_fresh(if_none_match: Option<&HeaderValue>, etag: &str) -> bool {
+    let if_none_match = match if_none_match.and_then(|value| value.to_str().ok()) {
+        Some(if_none_match) => if_none_match,
+        None => return false,
+    };
+    // `If-None-Match` uses the weak comparison
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::http::HeaderValue;
+
+    use super::is_fresh;
+
+    #[test]
+    fn test_is_fresh() {
+        let etag = "\"abc\"";
+        let fresh = |header: &'static str| is_fresh(Some(&HeaderValue::from_static(header)), etag);
+
+        assert!(fresh("\"abc\""));
+        assert!(fresh("W/\"abc\""));
+        assert!(fresh("\"xyz\", \"abc\""));
+        assert!(fresh("*"));
+        assert!(!fresh("\"xyz\""));
+        assert!(!fresh("abc"));
+        assert!(!is_fresh(None, etag));
+    }
+}
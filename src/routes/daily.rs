@@ -0,0 +1,313 @@
+//! The Pokémon of the day.
+//!
+//! Each calendar day deterministically maps to a species and one of its flavor texts. The
+//! description of the next day is translated by a background task ahead of time, so that it
+//! is ready right at midnight. Requests are only served from memory and never consume any
+//! translation quota.
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::api_clients::ApiError;
+use crate::config::DailyConfig;
+use crate::metrics;
+use crate::routes::error_response;
+use crate::routes::pokemon::{negotiate, respond, DescriptionQuery, ShakespearedDescription};
+use crate::routes::translation::{translate, TranslationError};
+use crate::translation_cache::TranslationCache;
+
+/// Upper bound of species drawn to find one with an English description.
+const MAX_ATTEMPTS: usize = 25;
+
+/// Possible errors while preparing the Pokémon of the day.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum DailyError {
+    #[error(transparent)]
+    Api(#[from] ApiError),
+    #[error(transparent)]
+    Translation(#[from] TranslationError),
+    #[error("None of the drawn species has an English description")]
+    NoDescription,
+}
+
+/// The Pokémon of the day, shared between the background task and the handlers.
+#[derive(Debug)]
+pub struct Daily {
+    timezone: Tz,
+    retry_interval: Duration,
+    /// The latest description up to today and the ones prepared for the next days
+    prepared: RwLock<BTreeMap<NaiveDate, ShakespearedDescription>>,
+}
+
+impl Daily {
+    pub fn new(config: &DailyConfig) -> Self {
+        Self {
+            timezone: config.timezone,
+            retry_interval: Duration::from_secs(config.retry_interval_secs),
+            prepared: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// The calendar day in the configured time zone.
+    fn date(&self, now: DateTime<Utc>) -> NaiveDate {
+        now.with_timezone(&self.timezone).date().naive_local()
+    }
+
+    /// Time left until the next day starts in the configured time zone.
+    fn until_midnight(&self, now: DateTime<Utc>) -> Duration {
+        let midnight = self.date(now).succ().and_hms(0, 0, 0);
+        // a few time zones skip midnight when switching to daylight saving time
+        let next_day = self
+            .timezone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                    .earliest()
+            });
+        next_day
+            .and_then(|next_day| (next_day.with_timezone(&Utc) - now).to_std().ok())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn is_prepared(&self, date: NaiveDate) -> bool {
+        self.prepared.read().unwrap().contains_key(&date)
+    }
+
+    /// Keep the description of a day, forgetting all but the latest one before today.
+    fn insert(&self, date: NaiveDate, description: ShakespearedDescription, today: NaiveDate) {
+        let mut prepared = self.prepared.write().unwrap();
+        prepared.insert(date, description);
+        if let Some(&latest) = prepared.range(..=today).next_back().map(|(date, _)| date) {
+            *prepared = prepared.split_off(&latest);
+        }
+    }
+
+    /// Serve the description of the given day, with the date of the latest description up to
+    /// that day if the day itself isn't prepared.
+    fn serve<T>(
+        &self,
+        today: NaiveDate,
+        serve: impl FnOnce(NaiveDate, &ShakespearedDescription) -> T,
+    ) -> Option<T> {
+        let prepared = self.prepared.read().unwrap();
+        let (date, description) = prepared.range(..=today).next_back()?;
+        Some(serve(*date, description))
+    }
+
+    /// Pick and translate the Pokémon of the given day.
+    #[tracing::instrument(
+        name = "Prepare the Pokémon of the day",
+        skip(self, poke_api, translate_api, translation_cache)
+    )]
+    async fn prepare(
+        &self,
+        date: NaiveDate,
+        poke_api: &PokeApi,
+        translate_api: &TranslationApi,
+        translation_cache: &TranslationCache,
+    ) -> Result<ShakespearedDescription, DailyError> {
+        let seed = seed(date);
+        let species_names = poke_api.list_species().await?;
+        let drawn = species_names
+            .choose_multiple(&mut StdRng::seed_from_u64(seed), MAX_ATTEMPTS)
+            .collect::<Vec<_>>();
+        for name in drawn {
+            let species = poke_api.get_pokemon_species_description(name).await?;
+            if let Some(choice) = DescriptionQuery::seeded(seed).choose(&species) {
+                // cached descriptions don't cost any quota, new ones are cached for requests
+                let translation = translate(
+                    choice.flavor_text(),
+                    Some(species.name()),
+                    translate_api,
+                    translation_cache,
+                    None,
+                )
+                .await?;
+                return Ok(ShakespearedDescription::new(name.clone(), translation));
+            }
+        }
+        Err(DailyError::NoDescription)
+    }
+}
+
+/// The same seed is used for the species and its flavor text.
+fn seed(date: NaiveDate) -> u64 {
+    date.num_days_from_ce() as u64
+}
+
+/// Prepare the Pokémon of today and of the next day until the surrounding runtime stops.
+///
+/// The next day is prepared ahead of time, so that it is served right at midnight. Failed
+/// preparations, e.g. due to an exhausted translation quota, are retried after the configured
+/// interval or once the quota resets. Meanwhile the previous Pokémon is still served.
+pub async fn refresh(
+    daily: web::Data<Daily>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+) {
+    loop {
+        let today = daily.date(Utc::now());
+        let unprepared = [today, today.succ()]
+            .iter()
+            .copied()
+            .find(|date| !daily.is_prepared(*date));
+        let date = match unprepared {
+            Some(date) => date,
+            None => {
+                actix_web::rt::time::sleep(daily.until_midnight(Utc::now())).await;
+                continue;
+            }
+        };
+        match daily
+            .prepare(date, &poke_api, &translate_api, &translation_cache)
+            .await
+        {
+            Ok(description) => {
+                tracing::info!("Prepared the Pokémon of {}", date);
+                daily.insert(date, description, daily.date(Utc::now()));
+            }
+            Err(e) => {
+                tracing::warn!("Failed to prepare the Pokémon of {}: {}", date, e);
+                let retry_after = translate_api
+                    .quota_exhausted_for()
+                    .unwrap_or(daily.retry_interval);
+                let until_midnight = daily.until_midnight(Utc::now());
+                actix_web::rt::time::sleep(retry_after.min(until_midnight)).await;
+            }
+        }
+    }
+}
+
+/// Handler for the Pokémon of the day endpoint.
+///
+/// Returns the description prepared by the background task, which may be cached until the
+/// next midnight. If today's description isn't ready, the previous one is served without
+/// caching. Until the first one is ready, requests are answered with a 503 status.
+#[tracing::instrument(name = "Return the Pokémon of the day", skip(req, daily))]
+pub async fn daily(
+    req: HttpRequest,
+    daily: Option<web::Data<Daily>>,
+) -> Result<HttpResponse, HttpResponse> {
+    let daily = daily.ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_FOUND,
+            "The Pokémon of the day is not enabled.",
+        )
+    })?;
+    let media_type = negotiate(&req)?;
+
+    let now = Utc::now();
+    let today = daily.date(now);
+    let response = daily.serve(today, |date, description| {
+        let fresh = date == today;
+        metrics::observe_cache_lookup("daily", fresh);
+        let max_age = fresh.then(|| daily.until_midnight(now));
+        respond(&req, media_type, description, max_age)
+    });
+    response.ok_or_else(|| {
+        metrics::observe_cache_lookup("daily", false);
+        error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The Pokémon of the day is not ready yet, try again later.",
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::sync::RwLock;
+    use std::time::Duration;
+
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use crate::routes::pokemon::ShakespearedDescription;
+
+    use super::Daily;
+
+    fn daily(timezone: Tz) -> Daily {
+        Daily {
+            timezone,
+            retry_interval: Duration::from_secs(300),
+            prepared: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    #[test]
+    fn test_serve() {
+        let daily = daily(Tz::UTC);
+        let day = |d| NaiveDate::from_ymd(2021, 6, d);
+        let description = |name: &str| ShakespearedDescription::new(name.into(), "Yond".into());
+        let served = |today| daily.serve(today, |date, _| date);
+        assert_eq!(served(day(1)), None);
+
+        // the next day is served from midnight on
+        daily.insert(day(1), description("bulbasaur"), day(1));
+        daily.insert(day(2), description("ivysaur"), day(1));
+        assert_eq!(served(day(1)), Some(day(1)));
+        assert_eq!(served(day(2)), Some(day(2)));
+
+        // the previous day is served until the current one is prepared
+        assert_eq!(served(day(3)), Some(day(2)));
+        daily.insert(day(4), description("charmander"), day(3));
+        assert_eq!(served(day(3)), Some(day(2)));
+
+        // only the latest day before today is kept
+        daily.insert(day(3), description("venusaur"), day(3));
+        let prepared = daily.prepared.read().unwrap();
+        assert_eq!(
+            prepared.keys().copied().collect::<Vec<_>>(),
+            [day(3), day(4)]
+        );
+    }
+
+    #[test]
+    fn test_date() {
+        let now = Utc.ymd(2021, 6, 30).and_hms(23, 30, 0);
+        assert_eq!(daily(Tz::UTC).date(now), NaiveDate::from_ymd(2021, 6, 30));
+        assert_eq!(
+            daily(Tz::Europe__Berlin).date(now),
+            NaiveDate::from_ymd(2021, 7, 1)
+        );
+        assert_eq!(
+            daily(Tz::America__New_York).date(now),
+            NaiveDate::from_ymd(2021, 6, 30)
+        );
+    }
+
+    #[test]
+    fn test_until_midnight() {
+        let now = Utc.ymd(2021, 6, 30).and_hms(23, 30, 0);
+        assert_eq!(
+            daily(Tz::UTC).until_midnight(now),
+            Duration::from_secs(30 * 60)
+        );
+        // Berlin is two hours ahead in summer
+        assert_eq!(
+            daily(Tz::Europe__Berlin).until_midnight(now),
+            Duration::from_secs(22 * 60 * 60 + 30 * 60)
+        );
+        // Santiago skipped midnight when switching to daylight saving time in 2021
+        let now = Utc.ymd(2021, 9, 4).and_hms(12, 0, 0);
+        let santiago = daily(Tz::America__Santiago);
+        assert_eq!(
+            santiago.until_midnight(now),
+            Duration::from_secs(16 * 60 * 60)
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use actix_web::http::StatusCode;
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+
+use crate::api_clients::pokeapi::{EvolutionStage, PokeApi};
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::auth::ApiClient;
+use crate::routes::caching::{cacheable, uncacheable, MAX_AGE};
+use crate::routes::pokemon::{lookup_species, DescriptionQuery};
+use crate::routes::translation::{translate, Outcome};
+use crate::routes::{error_response, lookup_error};
+use crate::species_index::SpeciesIndex;
+use crate::translation_cache::TranslationCache;
+
+/// Number of species fetched from the PokéAPI concurrently.
+const CONCURRENT_FETCHES: usize = 4;
+
+/// Handler for the evolution chain endpoint.
+///
+/// Returns every stage of the evolution chain of a Pokémon with a shakespeare-ified
+/// description and an individual status, like the batch endpoint. The `version` and `seed`
+/// query parameters select the descriptions like for a single Pokémon, and names are looked up
+/// the same way, i.e. varieties are resolved to the chain of their species.
+///
+/// # Implementation Detail
+///
+/// A chain has up to nine stages, translating all of them on every request would exhaust the
+/// translation quota right away. Translations are thus served from the translation cache if
+/// possible, only the remaining ones are translated until the quota is exhausted.
+#[tracing::instrument(
+    name = "Return shakespeared descriptions of an evolution chain",
+    skip(req, poke_api, translate_api, translation_cache, client)
+)]
+pub async fn evolutions(
+    req: HttpRequest,
+    pokemon_name: web::Path<String>,
+    query: web::Query<DescriptionQuery>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    // the index is optional, see `species_index`
+    let species_index = req.app_data::<web::Data<SpeciesIndex>>();
+    let species_index = species_index.map(|index| index.get_ref());
+    let species = lookup_species(&pokemon_name, &poke_api, species_index)
+        .await
+        .map_err(|(status, message)| error_response(status, message))?;
+    let chain_id = species
+        .evolution_chain_id()
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "No evolution chain found."))?;
+    let chain = poke_api
+        .get_evolution_chain(chain_id)
+        .await
+        .map_err(|e| lookup_error(e, "No evolution chain found."))?;
+    let stages = chain.stages();
+
+    // the requested species has been fetched already
+    let mut stage_species = stream::iter(stages.iter().filter(|s| s.name != species.name()))
+        .map(|stage| {
+            let poke_api = &poke_api;
+            async move {
+                let species = poke_api.get_pokemon_species_description(stage.name).await;
+                (stage.name, species)
+            }
+        })
+        .buffer_unordered(CONCURRENT_FETCHES)
+        .collect::<HashMap<_, _>>()
+        .await;
+    if let Some(stage) = stages.iter().find(|s| s.name == species.name()) {
+        stage_species.insert(stage.name, Ok(species));
+    }
+
+    let mut results = Vec::with_capacity(stages.len());
+    for stage in &stages {
+        let outcome = match &stage_species[stage.name] {
+            Ok(species) => match query.choose(species) {
+                Some(choice) => translate(
+                    choice.flavor_text(),
+                    Some(species.name()),
+                    &translate_api,
+                    &translation_cache,
+                    client.as_deref(),
+                )
+                .await
+                .map_err(Into::into),
+                None => Err((StatusCode::NOT_FOUND, "No English description found.")),
+            },
+            Err(e) => {
+                tracing::error!("{}", e);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error."))
+            }
+        };
+        results.push(EvolutionStageItem::new(stage, outcome));
+    }
+
+    let complete = results.iter().all(|item| item.description.is_some());
+    let body = serde_json::to_vec(&EvolutionsResponse {
+        chain_id,
+        stages: results,
+    })
+    .expect("Failed to serialize evolutions");
+    // partial results must not be cached
+    if complete && query.is_deterministic() {
+        Ok(cacheable(&req, mime::APPLICATION_JSON, body, MAX_AGE))
+    } else {
+        Ok(uncacheable(mime::APPLICATION_JSON, body))
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct EvolutionsResponse {
+    chain_id: u32,
+    stages: Vec<EvolutionStageItem>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EvolutionStageItem {
+    name: String,
+    stage: u32,
+    evolves_from: Option<String>,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl EvolutionStageItem {
+    fn new(stage: &EvolutionStage<'_>, outcome: Outcome) -> Self {
+        let (status, description, error) = match outcome {
+            Ok(description) => (StatusCode::OK, Some(description), None),
+            Err((status, error)) => (status, None, Some(error.to_string())),
+        };
+        EvolutionStageItem {
+            name: stage.name.to_string(),
+            stage: stage.stage,
+            evolves_from: stage.evolves_from.map(str::to_string),
+            status: status.as_u16(),
+            description,
+            error,
+        }
+    }
+}
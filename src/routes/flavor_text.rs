@@ -0,0 +1,214 @@
+//! Shakespeare-ified descriptions of PokéAPI resources besides species, e.g. moves and items.
+//!
+//! All of them go through the same pipeline as Pokémon descriptions: a flavor text is chosen
+//! according to the `version` and `seed` query parameters, translated and rendered in the
+//! negotiated representation.
+use std::future::Future;
+
+use actix_web::http::StatusCode;
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+
+use crate::api_clients::pokeapi::{
+    AbilityResponse, FlavorTexts, ItemResponse, MoveResponse, PokeApi,
+};
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::auth::ApiClient;
+use crate::routes::caching::MAX_AGE;
+use crate::routes::pokemon::{negotiate, respond, DescriptionQuery, ShakespearedDescription};
+use crate::routes::translation::translate;
+use crate::routes::{error_response, lookup_error};
+use crate::translation_cache::TranslationCache;
+
+/// The resource, or the error response for unknown names.
+pub type LookupResult<R> = Result<R, HttpResponse>;
+
+/// Look up a move, e.g. `thunderbolt`.
+pub async fn lookup_move(poke_api: web::Data<PokeApi>, name: String) -> LookupResult<MoveResponse> {
+    poke_api
+        .get_move(&name)
+        .await
+        .map_err(|e| lookup_error(e, "Unknown move."))
+}
+
+/// Look up an ability, e.g. `blaze`.
+pub async fn lookup_ability(
+    poke_api: web::Data<PokeApi>,
+    name: String,
+) -> LookupResult<AbilityResponse> {
+    poke_api
+        .get_ability(&name)
+        .await
+        .map_err(|e| lookup_error(e, "Unknown ability."))
+}
+
+/// Look up an item, e.g. `potion`.
+pub async fn lookup_item(poke_api: web::Data<PokeApi>, name: String) -> LookupResult<ItemResponse> {
+    poke_api
+        .get_item(&name)
+        .await
+        .map_err(|e| lookup_error(e, "Unknown item."))
+}
+
+/// Look up the item of a berry, e.g. `oran-berry` for `oran`.
+///
+/// Berries don't have any flavor texts themselves, they are described like their item.
+pub async fn lookup_berry(
+    poke_api: web::Data<PokeApi>,
+    name: String,
+) -> LookupResult<ItemResponse> {
+    let berry = poke_api
+        .get_berry(&name)
+        .await
+        .map_err(|e| lookup_error(e, "Unknown berry."))?;
+    let item = berry.item().to_string();
+    lookup_item(poke_api, item).await
+}
+
+/// Handler for move descriptions, e.g. `/move/thunderbolt`.
+#[tracing::instrument(
+    name = "Return a shakespeared move description",
+    skip(req, poke_api, translate_api, translation_cache, client)
+)]
+pub async fn move_description(
+    req: HttpRequest,
+    name: web::Path<String>,
+    query: web::Query<DescriptionQuery>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    flavor_text(
+        &req,
+        name.into_inner(),
+        query.into_inner(),
+        poke_api,
+        &translate_api,
+        &translation_cache,
+        client.as_deref(),
+        lookup_move,
+    )
+    .await
+}
+
+/// Handler for ability descriptions, e.g. `/ability/blaze`.
+#[tracing::instrument(
+    name = "Return a shakespeared ability description",
+    skip(req, poke_api, translate_api, translation_cache, client)
+)]
+pub async fn ability_description(
+    req: HttpRequest,
+    name: web::Path<String>,
+    query: web::Query<DescriptionQuery>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    flavor_text(
+        &req,
+        name.into_inner(),
+        query.into_inner(),
+        poke_api,
+        &translate_api,
+        &translation_cache,
+        client.as_deref(),
+        lookup_ability,
+    )
+    .await
+}
+
+/// Handler for item descriptions, e.g. `/item/potion`.
+#[tracing::instrument(
+    name = "Return a shakespeared item description",
+    skip(req, poke_api, translate_api, translation_cache, client)
+)]
+pub async fn item_description(
+    req: HttpRequest,
+    name: web::Path<String>,
+    query: web::Query<DescriptionQuery>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    flavor_text(
+        &req,
+        name.into_inner(),
+        query.into_inner(),
+        poke_api,
+        &translate_api,
+        &translation_cache,
+        client.as_deref(),
+        lookup_item,
+    )
+    .await
+}
+
+/// Handler for berry descriptions, e.g. `/berry/oran`.
+///
+/// Berries are described through the flavor texts of their item, e.g. `oran-berry`.
+#[tracing::instrument(
+    name = "Return a shakespeared berry description",
+    skip(req, poke_api, translate_api, translation_cache, client)
+)]
+pub async fn berry_description(
+    req: HttpRequest,
+    name: web::Path<String>,
+    query: web::Query<DescriptionQuery>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    flavor_text(
+        &req,
+        name.into_inner(),
+        query.into_inner(),
+        poke_api,
+        &translate_api,
+        &translation_cache,
+        client.as_deref(),
+        lookup_berry,
+    )
+    .await
+}
+
+/// Describe a resource besides species, looked up by the given function, e.g. `lookup_move`.
+#[allow(clippy::too_many_arguments)]
+async fn flavor_text<R, F, Fut>(
+    req: &HttpRequest,
+    name: String,
+    query: DescriptionQuery,
+    poke_api: web::Data<PokeApi>,
+    translate_api: &TranslationApi,
+    translation_cache: &TranslationCache,
+    client: Option<&ApiClient>,
+    lookup: F,
+) -> Result<HttpResponse, HttpResponse>
+where
+    R: FlavorTexts,
+    F: FnOnce(web::Data<PokeApi>, String) -> Fut,
+    Fut: Future<Output = LookupResult<R>>,
+{
+    // negotiated up front to not waste any upstream calls
+    let media_type = negotiate(req)?;
+    let resource = lookup(poke_api, name.clone()).await?;
+    let choice = query
+        .choose(&resource)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "No English description found."))?;
+    let translation = translate(
+        choice.flavor_text(),
+        None,
+        translate_api,
+        translation_cache,
+        client,
+    )
+    .await
+    .map_err(|e| e.response())?;
+
+    let description = ShakespearedDescription::new(name, translation);
+    let max_age = query.is_deterministic().then_some(MAX_AGE);
+    Ok(respond(req, media_type, &description, max_age))
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+use serde::Serialize;
+
+use crate::api_clients::pokeapi::{PokeApi, PokemonSpeciesResponse};
+use crate::routes::caching::{cacheable, MAX_AGE};
+use crate::routes::error_response;
+use crate::routes::pokemon::lookup_species;
+use crate::species_index::SpeciesIndex;
+
+/// Handler for the species metadata endpoint.
+///
+/// Returns the Pokédex metadata of a Pokémon species, e.g. its genus, localized names and
+/// generation. Nothing is translated, so the metadata is always returned as cacheable JSON.
+/// Names are looked up like for a single Pokémon, i.e. varieties are described by their species.
+#[tracing::instrument(
+    name = "Return Pokémon species metadata",
+    skip(req, poke_api, species_index),
+    fields(poke_api_url = %poke_api.base_url())
+)]
+pub async fn info(
+    req: HttpRequest,
+    pokemon_name: web::Path<String>,
+    poke_api: web::Data<PokeApi>,
+    species_index: Option<web::Data<SpeciesIndex>>,
+) -> Result<HttpResponse, HttpResponse> {
+    let species_index = species_index.as_ref().map(|index| index.get_ref());
+    let species = lookup_species(&pokemon_name, &poke_api, species_index)
+        .await
+        .map_err(|(status, message)| error_response(status, message))?;
+
+    let body =
+        serde_json::to_vec(&SpeciesInfo::from(&species)).expect("Failed to serialize species info");
+    Ok(cacheable(&req, mime::APPLICATION_JSON, body, MAX_AGE))
+}
+
+/// The Pokédex metadata of a species.
+#[derive(Serialize, Debug)]
+pub struct SpeciesInfo<'a> {
+    id: u32,
+    name: &'a str,
+    /// English display name, e.g. `Charizard`
+    display_name: Option<&'a str>,
+    /// English genus, e.g. `Flame Pokémon`
+    genus: Option<&'a str>,
+    /// Names by language code
+    names: BTreeMap<&'a str, &'a str>,
+    /// Genera by language code
+    genera: BTreeMap<&'a str, &'a str>,
+    generation: &'a str,
+    habitat: Option<&'a str>,
+    color: &'a str,
+    shape: Option<&'a str>,
+    is_legendary: bool,
+    is_mythical: bool,
+}
+
+impl<'a> From<&'a PokemonSpeciesResponse> for SpeciesInfo<'a> {
+    fn from(species: &'a PokemonSpeciesResponse) -> Self {
+        SpeciesInfo {
+            id: species.id(),
+            name: species.name(),
+            display_name: species.english_name(),
+            genus: species.english_genus(),
+            names: species
+                .names()
+                .iter()
+                .map(|name| (name.language(), name.name()))
+                .collect(),
+            genera: species
+                .genera()
+                .iter()
+                .map(|genus| (genus.language(), genus.genus()))
+                .collect(),
+            generation: species.generation(),
+            habitat: species.habitat(),
+            color: species.color(),
+            shape: species.shape(),
+            is_legendary: species.is_legendary(),
+            is_mythical: species.is_mythical(),
+        }
+    }
+}
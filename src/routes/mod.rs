@@ -1,7 +1,94 @@
+pub mod admin;
+pub mod batch;
+pub mod caching;
+pub mod daily;
+pub mod evolutions;
+pub mod flavor_text;
+pub mod info;
+pub mod negotiation;
 pub mod pokemon;
+pub mod random;
+pub mod readiness;
+pub mod translation;
 
-use actix_web::HttpResponse;
+use std::time::Duration;
 
+use actix_web::error::{InternalError, PathError, QueryPayloadError};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::{HeaderValue, StatusCode};
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Serialize;
+
+use crate::api_clients::ApiError;
+use crate::request_id::RequestId;
+
+/// Liveness endpoint, only signals that the process is up and serving requests.
 pub async fn healthz() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+/// Fallback for unknown routes.
+pub async fn not_found() -> HttpResponse {
+    error_response(StatusCode::NOT_FOUND, "Not found.")
+}
+
+/// Turn malformed query strings into problem responses.
+pub fn query_error(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = error_response(
+        StatusCode::BAD_REQUEST,
+        &format!("Invalid query string: {}", err),
+    );
+    InternalError::from_response(err, response).into()
+}
+
+/// Turn path segments that can't be parsed into problem responses.
+pub fn path_error(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    let response = error_response(StatusCode::NOT_FOUND, &format!("Invalid path: {}", err));
+    InternalError::from_response(err, response).into()
+}
+
+/// Body of all error responses, RFC 7807 problem details.
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// Build an error response, tagged with the ID of the request currently being handled.
+pub fn error_response(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/problem+json")
+        .json(Problem {
+            // problems are only distinguished by their status
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or_default(),
+            status: status.as_u16(),
+            detail: message.to_string(),
+            request_id: RequestId::current().map(|id| id.to_string()),
+        })
+}
+
+/// Build a 429 error response asking the client to retry after the given duration.
+pub fn too_many_requests(message: &str, retry_after: Duration) -> HttpResponse {
+    let mut response = error_response(StatusCode::TOO_MANY_REQUESTS, message);
+    // round up, retrying too early would only be rejected again
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(secs));
+    response
+}
+
+/// Build the error response for a failed lookup of a PokéAPI resource.
+pub fn lookup_error(e: ApiError, not_found: &str) -> HttpResponse {
+    if e.is_not_found() {
+        return error_response(StatusCode::NOT_FOUND, not_found);
+    }
+    tracing::error!("{}", e);
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.")
+}
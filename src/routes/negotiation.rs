@@ -0,0 +1,124 @@
+//! Content negotiation through the `Accept` header.
+use actix_web::http::header::{q, Accept, Header, Quality, QualityItem};
+use actix_web::HttpRequest;
+use mime::Mime;
+
+/// The representations of a description, in order of our preference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaType {
+    Json,
+    Text,
+    Html,
+}
+
+impl MediaType {
+    const ALL: [MediaType; 3] = [MediaType::Json, MediaType::Text, MediaType::Html];
+
+    /// Pick the representation the client prefers.
+    ///
+    /// Clients without a valid `Accept` header get JSON, `None` means that none of our
+    /// representations is acceptable.
+    pub fn negotiate(req: &HttpRequest) -> Option<Self> {
+        let accept = match Accept::parse(req) {
+            Ok(accept) if !accept.is_empty() => accept,
+            _ => return Some(MediaType::Json),
+        };
+        Self::ALL
+            .iter()
+            .filter_map(|media_type| {
+                let quality = quality(&accept, &media_type.mime())?;
+                Some((*media_type, quality))
+            })
+            .filter(|(_, quality)| *quality > q(0))
+            // `max_by_key` returns the last maximum, we prefer the first one
+            .rev()
+            .max_by_key(|(_, quality)| *quality)
+            .map(|(media_type, _)| media_type)
+    }
+
+    pub fn mime(self) -> Mime {
+        match self {
+            MediaType::Json => mime::APPLICATION_JSON,
+            MediaType::Text => mime::TEXT_PLAIN_UTF_8,
+            MediaType::Html => mime::TEXT_HTML_UTF_8,
+        }
+    }
+}
+
+/// The quality of the most specific range matching the given type, if any.
+fn quality(accept: &Accept, mime: &Mime) -> Option<Quality> {
+    accept
+        .iter()
+        .filter_map(|QualityItem { item, quality }| {
+            let specificity = match (item.type_(), item.subtype()) {
+                (mime::STAR, mime::STAR) => 0,
+                (type_, mime::STAR) if type_ == mime.type_() => 1,
+                (type_, subtype) if type_ == mime.type_() && subtype == mime.subtype() => 2,
+                _ => return None,
+            };
+            Some((specificity, *quality))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, quality)| quality)
+}
+
+/// Escape text for use in HTML content and attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test::TestRequest;
+
+    use super::{escape_html, MediaType};
+
+    fn negotiate(accept: Option<&str>) -> Option<MediaType> {
+        let mut req = TestRequest::default();
+        if let Some(accept) = accept {
+            req = req.insert_header(("Accept", accept));
+        }
+        MediaType::negotiate(&req.to_http_request())
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None), Some(MediaType::Json));
+        assert_eq!(negotiate(Some("*/*")), Some(MediaType::Json));
+        assert_eq!(negotiate(Some("text/plain")), Some(MediaType::Text));
+        assert_eq!(negotiate(Some("text/*")), Some(MediaType::Text));
+        assert_eq!(
+            negotiate(Some("text/html,application/xhtml+xml,*/*;q=0.8")),
+            Some(MediaType::Html)
+        );
+        assert_eq!(
+            negotiate(Some("text/plain;q=0.5, application/json;q=0.2")),
+            Some(MediaType::Text)
+        );
+        // the most specific range wins
+        assert_eq!(
+            negotiate(Some("*/*, application/json;q=0")),
+            Some(MediaType::Text)
+        );
+        assert_eq!(negotiate(Some("image/png")), None);
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<b>\"Tom\" & 'Jerry'</b>"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+    }
+}
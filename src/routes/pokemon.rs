@@ -1,77 +1,321 @@
+use std::time::Duration;
+
+use actix_web::http::header::VARY;
+use actix_web::http::{HeaderValue, StatusCode};
 use actix_web::web::{self, HttpResponse};
-use rand::{thread_rng, Rng};
-use serde::Serialize;
+use actix_web::HttpRequest;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::pokeapi::{FlavorTexts, FlavourTextEntry, PokeApi, PokemonSpeciesResponse};
 use crate::api_clients::shakespeare::TranslationApi;
-use crate::api_clients::ApiError;
+use crate::auth::ApiClient;
+use crate::routes::caching::{cacheable, uncacheable, MAX_AGE};
+use crate::routes::error_response;
+use crate::routes::negotiation::{escape_html, MediaType};
+use crate::routes::translation::translate;
+use crate::species_index::SpeciesIndex;
+use crate::translation_cache::TranslationCache;
 
 /// Handler for the Shakespeare meets Pokémon endpoint.
 ///
 /// Given a Pokémon name in the path, it returns a shakespeare-ified description of the
-/// Pokémon species.
+/// Pokémon species. Varieties like `charizard-mega-x` are described by their species. The
+/// `version` and `seed` query parameters select a description deterministically, such
+/// responses carry an `ETag` and can be cached. With `title=true`, a translated heading built
+/// from the species' name and genus is added, unless it can't be translated. Depending on the
+/// `Accept` header, the description is returned as JSON, plain text or an HTML card. With the
+/// species index enabled, unknown names are rejected without any upstream call.
 ///
 /// # Implementation Detail
 ///
 /// The translation is based on api.funtranslations.com which has strict rate-limitting.
 /// We only get up to 5 requests per hour and 60 per day on the free tier. Upon reaching
-/// the rate limit, we forward the 429 status to the caller.
+/// the rate limit, we forward the 429 status to the caller. Authenticated clients are
+/// additionally limited by the translation budget of their API key.
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     name = "Return a shakespeared Pokémon description",
-    skip(poke_api, translate_api),
+    skip(req, poke_api, translate_api, translation_cache, client),
     fields(
         poke_api_url = %poke_api.base_url(),
         translate_api_url = %translate_api.base_url(),
     )
 )]
 pub async fn pokemon(
+    req: HttpRequest,
     pokemon_name: web::Path<String>,
+    query: web::Query<DescriptionQuery>,
+    title_query: web::Query<TitleQuery>,
     poke_api: web::Data<PokeApi>,
     translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    client: Option<web::ReqData<ApiClient>>,
 ) -> Result<HttpResponse, HttpResponse> {
-    let pokemon_response = poke_api
-        .get_pokemon_species_description(&*pokemon_name)
-        .await
-        .map_err(|e| {
-            tracing::error!("{}", e);
-            HttpResponse::InternalServerError().finish()
-        })?;
-
-    // always returning the same trivia is boring, mix it up a bit through randomization
-    let mut rng = thread_rng();
-    let english_flavor_texts = pokemon_response
-        .english_flavor_text_entries()
-        .collect::<Vec<_>>();
-    let choice_idx = rng.gen_range(0..english_flavor_texts.len());
-    let choice = english_flavor_texts[choice_idx];
-
-    let translation = translate_api
-        .translate(choice.flavor_text())
-        .await
-        .map_err(|e| {
-            tracing::error!("{}", e);
-            // funtranslations API has a strict RateLimit on the free tier with max 5/h
-            // provide some
-            if matches!(&e, &ApiError::RateLimit(_)) {
-                return HttpResponse::TooManyRequests().json("Too many requests, try again later.");
+    // negotiated up front to not waste any upstream calls
+    let media_type = negotiate(&req)?;
+    // the index is optional, see `species_index`
+    let species_index = req.app_data::<web::Data<SpeciesIndex>>();
+    let pokemon_response = lookup_species(
+        &pokemon_name,
+        &poke_api,
+        species_index.map(|index| index.get_ref()),
+    )
+    .await
+    .map_err(|(status, message)| error_response(status, message))?;
+
+    let choice = query
+        .choose(&pokemon_response)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "No English description found."))?;
+    let translation = translate(
+        choice.flavor_text(),
+        Some(pokemon_response.name()),
+        &translate_api,
+        &translation_cache,
+        client.as_deref(),
+    )
+    .await
+    .map_err(|e| e.response())?;
+
+    let mut description = ShakespearedDescription::new(pokemon_name.into_inner(), translation);
+    if title_query.title {
+        if let Some(title) = compose_title(&pokemon_response, choice.flavor_text()) {
+            // the title is optional, the description is returned without it
+            let species = Some(pokemon_response.name());
+            let client = client.as_deref();
+            match translate(&title, species, &translate_api, &translation_cache, client).await {
+                Ok(title) => description = description.with_title(title),
+                Err(e) => tracing::warn!("Failed to translate the title: {:?}", e),
             }
-            HttpResponse::InternalServerError().finish()
-        })?;
+        }
+    }
+    let max_age = query.is_deterministic().then_some(MAX_AGE);
+    Ok(respond(&req, media_type, &description, max_age))
+}
+
+/// Pick the representation of a description before making any upstream calls.
+pub(crate) fn negotiate(req: &HttpRequest) -> Result<MediaType, HttpResponse> {
+    MediaType::negotiate(req).ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_ACCEPTABLE,
+            "Only JSON, plain text and HTML are supported.",
+        )
+    })
+}
 
-    Ok(HttpResponse::Ok().json(ShakespearedDescription::new(
-        pokemon_name.into_inner(),
-        translation,
-    )))
+/// Look up the species of a Pokémon by its species or variety name.
+///
+/// With the species index enabled, unknown names are rejected without any upstream call.
+pub(crate) async fn lookup_species(
+    name: &str,
+    poke_api: &PokeApi,
+    species_index: Option<&SpeciesIndex>,
+) -> Result<PokemonSpeciesResponse, (StatusCode, &'static str)> {
+    if species_index.is_some_and(|index| index.rejects(name)) {
+        return Err((StatusCode::NOT_FOUND, "Unknown Pokémon."));
+    }
+    poke_api.resolve_species(name).await.map_err(|e| {
+        if e.is_not_found() {
+            return (StatusCode::NOT_FOUND, "Unknown Pokémon.");
+        }
+        tracing::error!("{}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.")
+    })
+}
+
+/// Respond with the description in the negotiated representation.
+///
+/// Only deterministic descriptions may be cached, for up to `max_age`.
+pub(crate) fn respond(
+    req: &HttpRequest,
+    media_type: MediaType,
+    description: &ShakespearedDescription,
+    max_age: Option<Duration>,
+) -> HttpResponse {
+    let body = description.render(media_type);
+    let mut response = match max_age {
+        Some(max_age) => cacheable(req, media_type.mime(), body, max_age),
+        None => uncacheable(media_type.mime(), body),
+    };
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// Optional query parameters selecting the description.
+#[derive(Deserialize, Debug, Default)]
+pub struct DescriptionQuery {
+    /// Only pick descriptions of this game version, e.g. `red`
+    version: Option<String>,
+    /// Seed for picking the description
+    seed: Option<u64>,
+}
+
+impl DescriptionQuery {
+    /// Deterministically pick among the descriptions of all versions.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            version: None,
+            seed: Some(seed),
+        }
+    }
+
+    /// Whether the same description is returned for each request.
+    pub fn is_deterministic(&self) -> bool {
+        self.version.is_some() || self.seed.is_some()
+    }
+
+    /// Choose one of the English flavor texts of a species, move etc., if there are any.
+    pub fn choose<'a>(&self, resource: &'a impl FlavorTexts) -> Option<&'a FlavourTextEntry> {
+        let english_flavor_texts = resource
+            .english_flavor_text_entries()
+            .filter(|entry| self.version.as_deref().is_none_or(|v| entry.version() == v))
+            .collect::<Vec<_>>();
+        if english_flavor_texts.is_empty() {
+            return None;
+        }
+        // always returning the same trivia is boring, mix it up a bit through randomization
+        // unless the client asked for a deterministic description
+        let choice_idx = match (self.seed, &self.version) {
+            (Some(seed), _) => StdRng::seed_from_u64(seed).gen_range(0..english_flavor_texts.len()),
+            (None, Some(_)) => 0,
+            (None, None) => thread_rng().gen_range(0..english_flavor_texts.len()),
+        };
+        Some(english_flavor_texts[choice_idx])
+    }
+}
+
+/// Optional query parameter adding a translated title, at the cost of a second translation.
+#[derive(Deserialize, Debug, Default)]
+pub struct TitleQuery {
+    #[serde(default)]
+    title: bool,
+}
+
+/// Compose a heading like "Charizard, the Flame Pokémon, that spits fire." for translation.
+///
+/// The heading continues with the first sentence of the flavor text, `None` if the species
+/// lacks an English name or genus.
+pub fn compose_title(species: &PokemonSpeciesResponse, flavor_text: &str) -> Option<String> {
+    let name = species.english_name()?;
+    let genus = species.english_genus()?;
+    // flavor texts are wrapped for the game's text boxes
+    let flavor_text = flavor_text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let sentence = flavor_text
+        .split_terminator(['.', '!', '?'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    let mut chars = sentence.chars();
+    match chars.next() {
+        Some(first) => Some(format!(
+            "{}, the {}, that {}{}.",
+            name,
+            genus,
+            first.to_lowercase(),
+            chars.as_str()
+        )),
+        None => Some(format!("{}, the {}.", name, genus)),
+    }
 }
 
 #[derive(Serialize, Debug)]
 pub struct ShakespearedDescription {
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
     description: String,
 }
 
 impl ShakespearedDescription {
     pub fn new(name: String, description: String) -> Self {
-        Self { name, description }
+        Self {
+            name,
+            title: None,
+            description,
+        }
+    }
+
+    /// Add a translated title.
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Render the description in the given representation.
+    pub fn render(&self, media_type: MediaType) -> Vec<u8> {
+        match media_type {
+            MediaType::Json => serde_json::to_vec(self).expect("Failed to serialize description"),
+            MediaType::Text => match &self.title {
+                Some(title) => format!("{}\n\n{}", title, self.description).into_bytes(),
+                None => self.description.clone().into_bytes(),
+            },
+            MediaType::Html => self.html_card().into_bytes(),
+        }
+    }
+
+    /// A small, self-contained HTML page showing the description as a card.
+    fn html_card(&self) -> String {
+        let name = escape_html(&self.name);
+        let title = self
+            .title
+            .as_deref()
+            .map(|title| format!("<p class=\"title\">{}</p>\n", escape_html(title)))
+            .unwrap_or_default();
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{name}</title>
+<style>
+body {{ margin: 0; min-height: 100vh; display: flex; align-items: center; justify-content: center; background: #f4ecd8; font-family: Georgia, serif; }}
+.card {{ max-width: 28em; margin: 1em; padding: 1.5em 2em; background: #fffaf0; border: 3px solid #8b5a2b; border-radius: 12px; box-shadow: 0 4px 12px rgba(0, 0, 0, 0.2); }}
+h1 {{ margin-top: 0; color: #8b0000; text-transform: capitalize; }}
+p {{ font-size: 1.2em; font-style: italic; line-height: 1.5; }}
+.title {{ font-size: 1em; font-style: normal; font-weight: bold; color: #5c3a1a; }}
+</style>
+</head>
+<body>
+<article class="card">
+<h1>{name}</h1>
+{title}<p>{description}</p>
+</article>
+</body>
+</html>
+"#,
+            name = name,
+            title = title,
+            description = escape_html(&self.description),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::api_clients::pokeapi::PokemonSpeciesResponse;
+
+    use super::compose_title;
+
+    static CHARIZARD_RESPONSE: &[u8] = include_bytes!("../../testdata/charizard.json");
+
+    #[test]
+    fn test_compose_title() {
+        let species: PokemonSpeciesResponse = serde_json::from_slice(CHARIZARD_RESPONSE).unwrap();
+        assert_eq!(
+            compose_title(
+                &species,
+                "Spits fire that\nis hot enough to\nmelt boulders.\u{0C}Known to cause\nforest fires."
+            )
+            .as_deref(),
+            Some("Charizard, the Flame Pokémon, that spits fire that is hot enough to melt boulders.")
+        );
+        assert_eq!(
+            compose_title(&species, "").as_deref(),
+            Some("Charizard, the Flame Pokémon.")
+        );
     }
 }
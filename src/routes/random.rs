@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use actix_web::http::StatusCode;
+use actix_web::web::{self, HttpResponse};
+use actix_web::HttpRequest;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::Deserialize;
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::api_clients::ApiError;
+use crate::auth::ApiClient;
+use crate::routes::error_response;
+use crate::routes::pokemon::{negotiate, respond, DescriptionQuery, ShakespearedDescription};
+use crate::routes::translation::translate;
+use crate::species_roster::{self, SpeciesRoster};
+use crate::translation_cache::TranslationCache;
+
+/// Upper bound of species fetched to find one matching the filters.
+const MAX_ATTEMPTS: usize = 25;
+
+/// Handler for the random Pokémon endpoint.
+///
+/// Picks a species uniformly from the PokéAPI species index and returns its shakespeare-ified
+/// description. The `generation`, `habitat` and `legendary` query parameters restrict the
+/// species to choose from. Random responses are never cached.
+///
+/// # Implementation Detail
+///
+/// Generations and habitats list their species, the legendary status however is only part of
+/// the species itself. The legendary species are thus scanned in the background on the first
+/// request asking for them, see `species_roster`. Until then, species are drawn without
+/// replacement until one matches, giving up after a few attempts with a 503 status.
+#[tracing::instrument(
+    name = "Return a shakespeared description of a random Pokémon",
+    skip(req, poke_api, translate_api, translation_cache, roster, client)
+)]
+pub async fn random(
+    req: HttpRequest,
+    query: web::Query<RandomQuery>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+    roster: web::Data<SpeciesRoster>,
+    client: Option<web::ReqData<ApiClient>>,
+) -> Result<HttpResponse, HttpResponse> {
+    let media_type = negotiate(&req)?;
+
+    let mut candidates = query.candidates(&poke_api, &roster).await.map_err(|e| {
+        if e.is_not_found() {
+            return error_response(StatusCode::NOT_FOUND, "Unknown generation or habitat.");
+        }
+        tracing::error!("{}", e);
+        error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.")
+    })?;
+    // only checked per species until the legendary species are known
+    let mut legendary_check = None;
+    if let Some(legendary) = query.legendary {
+        species_roster::scan_legendary(&roster, &poke_api);
+        match roster.legendary() {
+            Some(names) => candidates.retain(|name| names.contains(name) == legendary),
+            None => legendary_check = Some(legendary),
+        }
+    }
+
+    let attempts = candidates.choose_multiple(&mut thread_rng(), MAX_ATTEMPTS);
+    let attempts_len = attempts.len();
+    for name in attempts {
+        let species = poke_api
+            .get_pokemon_species_description(name)
+            .await
+            .map_err(|e| {
+                tracing::error!("{}", e);
+                error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.")
+            })?;
+        if legendary_check.is_some_and(|l| species.is_legendary() != l) {
+            continue;
+        }
+        let choice = match DescriptionQuery::default().choose(&species) {
+            Some(choice) => choice,
+            None => continue,
+        };
+        tracing::info!("Picked {}", name);
+        let translation = translate(
+            choice.flavor_text(),
+            Some(species.name()),
+            &translate_api,
+            &translation_cache,
+            client.as_deref(),
+        )
+        .await
+        .map_err(|e| e.response())?;
+        let description = ShakespearedDescription::new(name.clone(), translation);
+        return Ok(respond(&req, media_type, &description, None));
+    }
+
+    if attempts_len == candidates.len() {
+        Err(error_response(
+            StatusCode::NOT_FOUND,
+            "No matching Pokémon found.",
+        ))
+    } else {
+        Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No matching Pokémon found in time, try again.",
+        ))
+    }
+}
+
+/// Optional query parameters restricting the species to choose from.
+#[derive(Deserialize, Debug, Default)]
+pub struct RandomQuery {
+    /// Only species introduced in this generation, e.g. `generation-i` or `1`
+    generation: Option<String>,
+    /// Only species living in this habitat, e.g. `cave`
+    habitat: Option<String>,
+    /// Only (non-)legendary species
+    legendary: Option<bool>,
+}
+
+impl RandomQuery {
+    /// The names of all species matching the generation and habitat.
+    async fn candidates(
+        &self,
+        poke_api: &PokeApi,
+        roster: &SpeciesRoster,
+    ) -> Result<Vec<String>, ApiError> {
+        let mut candidates = match &self.generation {
+            Some(generation) => poke_api.generation_species(generation).await?,
+            None => roster.species(poke_api).await?.to_vec(),
+        };
+        if let Some(habitat) = &self.habitat {
+            let habitat = poke_api
+                .habitat_species(habitat)
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>();
+            candidates.retain(|name| habitat.contains(name));
+        }
+        Ok(candidates)
+    }
+}
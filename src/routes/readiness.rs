@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::web::{self, HttpResponse};
+use serde::Serialize;
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::api_clients::ApiError;
+use crate::metrics;
+
+/// Cached readiness state of the upstream APIs.
+///
+/// Kubernetes probes fairly often, so the upstreams are only probed again once the cached
+/// report is older than the configured TTL.
+#[derive(Debug)]
+pub struct Readiness {
+    ttl: Duration,
+    timeout: Duration,
+    cached: Mutex<Option<(Instant, ReadinessReport)>>,
+}
+
+impl Readiness {
+    /// How long a readiness report is reused before probing the upstreams again.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(10);
+    /// Timeout for each individual upstream probe.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Construct a new readiness check with the given cache TTL and probe timeout.
+    pub fn new(ttl: Duration, timeout: Duration) -> Self {
+        Self {
+            ttl,
+            timeout,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Return the cached report or probe all upstreams if it's stale.
+    pub async fn check(
+        &self,
+        poke_api: &PokeApi,
+        translate_api: &TranslationApi,
+    ) -> ReadinessReport {
+        if let Some((checked_at, report)) = &*self.cached.lock().unwrap() {
+            if checked_at.elapsed() < self.ttl {
+                metrics::observe_cache_lookup("readiness", true);
+                return report.clone();
+            }
+        }
+        metrics::observe_cache_lookup("readiness", false);
+
+        let (poke_api_status, translate_api_status) = futures::join!(
+            DependencyStatus::probe(poke_api.probe(self.timeout)),
+            DependencyStatus::probe(translate_api.probe(self.timeout)),
+        );
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert("poke_api", poke_api_status);
+        dependencies.insert("translation_api", translate_api_status);
+        let report = ReadinessReport {
+            ready: dependencies.values().all(|dep| dep.up),
+            dependencies,
+        };
+
+        *self.cached.lock().unwrap() = Some((Instant::now(), report.clone()));
+        report
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TTL, Self::DEFAULT_TIMEOUT)
+    }
+}
+
+/// Per-dependency breakdown returned by the readiness endpoint.
+#[derive(Serialize, Debug, Clone)]
+pub struct ReadinessReport {
+    ready: bool,
+    dependencies: BTreeMap<&'static str, DependencyStatus>,
+}
+
+impl ReadinessReport {
+    /// Whether all dependencies are up.
+    pub fn ready(&self) -> bool {
+        self.ready
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DependencyStatus {
+    up: bool,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    async fn probe(probe: impl std::future::Future<Output = Result<(), ApiError>>) -> Self {
+        let start = Instant::now();
+        let outcome = probe.await;
+        let latency_ms = start.elapsed().as_millis();
+        match outcome {
+            Ok(()) => Self {
+                up: true,
+                latency_ms,
+                error: None,
+            },
+            Err(e) => {
+                tracing::warn!("Readiness probe failed: {}", e);
+                Self {
+                    up: false,
+                    latency_ms,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Handler for the readiness endpoint.
+///
+/// Unlike `/healthz`, which only signals that the process is alive, this probes the PokéAPI
+/// and the translation API and returns 503 if either is unreachable or the translation
+/// quota is exhausted.
+#[tracing::instrument(name = "Check readiness", skip(readiness, poke_api, translate_api))]
+pub async fn readyz(
+    readiness: web::Data<Readiness>,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+) -> Result<HttpResponse, HttpResponse> {
+    let report = readiness.check(&poke_api, &translate_api).await;
+    if !report.ready() {
+        return Err(HttpResponse::ServiceUnavailable().json(report));
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
@@ -0,0 +1,113 @@
+//! Translation of flavor texts, shared by all endpoints returning descriptions.
+//!
+//! Cached translations are served without touching the translation quota or the client's
+//! translation budget. Only the remaining ones are translated, as long as the quota lasts.
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::api_clients::ApiError;
+use crate::auth::ApiClient;
+use crate::routes::{error_response, too_many_requests};
+use crate::translation_cache::TranslationCache;
+
+/// The outcome for a single item of a response, either a description or an error status and
+/// message.
+pub(crate) type Outcome = Result<String, (StatusCode, &'static str)>;
+
+/// Why a flavor text couldn't be translated.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("{message}")]
+pub(crate) struct TranslationError {
+    status: StatusCode,
+    message: &'static str,
+    /// When the client may retry, if known
+    retry_after: Option<Duration>,
+}
+
+impl TranslationError {
+    fn rate_limited(message: &'static str, retry_after: Option<Duration>) -> Self {
+        Self {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            message,
+            retry_after,
+        }
+    }
+
+    /// Build the error response for a request translating a single text.
+    pub(crate) fn response(&self) -> HttpResponse {
+        match self.retry_after {
+            Some(retry_after) => too_many_requests(self.message, retry_after),
+            None => error_response(self.status, self.message),
+        }
+    }
+}
+
+impl From<TranslationError> for (StatusCode, &'static str) {
+    fn from(e: TranslationError) -> Self {
+        (e.status, e.message)
+    }
+}
+
+/// Translate a flavor text or title, charging the translation budget of the client unless the
+/// translation is cached.
+///
+/// The species described by the text, if any, is recorded along with the cached translation.
+pub(crate) async fn translate(
+    flavor_text: &str,
+    species: Option<&str>,
+    translate_api: &TranslationApi,
+    translation_cache: &TranslationCache,
+    client: Option<&ApiClient>,
+) -> Result<String, TranslationError> {
+    // cached translations don't count against the quota or the client's budget
+    if let Some(translation) = translation_cache.get(flavor_text) {
+        if let Some(species) = species {
+            translation_cache.add_species(flavor_text, species);
+        }
+        return Ok(translation);
+    }
+    // funtranslations API has a strict RateLimit on the free tier with max 5/h, don't waste
+    // any calls while it is exhausted
+    if let Some(exhausted_for) = translate_api.quota_exhausted_for() {
+        return Err(TranslationError::rate_limited(
+            "Too many requests, try again later.",
+            Some(exhausted_for),
+        ));
+    }
+    if let Some(client) = client {
+        client.acquire_translation().map_err(|retry_after| {
+            tracing::info!("Translation budget of {} is exhausted", client.name());
+            TranslationError::rate_limited(
+                "Translation budget exceeded, try again later.",
+                Some(retry_after),
+            )
+        })?;
+    }
+
+    let translation = translate_api.translate(flavor_text).await.map_err(|e| {
+        tracing::error!("{}", e);
+        // failed translations don't count against the client's budget
+        if let Some(client) = client {
+            client.release_translation();
+        }
+        if matches!(&e, &ApiError::RateLimit(_)) {
+            return TranslationError::rate_limited(
+                "Too many requests, try again later.",
+                translate_api.quota_exhausted_for(),
+            );
+        }
+        TranslationError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Internal server error.",
+            retry_after: None,
+        }
+    })?;
+    translation_cache.insert(flavor_text, translation.clone());
+    if let Some(species) = species {
+        translation_cache.add_species(flavor_text, species);
+    }
+    Ok(translation)
+}
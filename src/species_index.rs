@@ -0,0 +1,110 @@
+//! In-memory index of the known Pokémon names.
+//!
+//! The index is fetched from the live PokéAPI in the background and refreshed periodically, so unknown
+//! names can be rejected without any upstream call while new species are still picked up.
+use std::collections::HashSet;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use actix_web::web;
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::ApiError;
+use crate::config::SpeciesIndexConfig;
+
+/// The names of all species and their varieties, e.g. `charizard` and `charizard-mega-x`.
+#[derive(Debug)]
+pub struct SpeciesIndex {
+    refresh_interval: Duration,
+    retry_interval: Duration,
+    names: RwLock<Option<HashSet<String>>>,
+}
+
+impl SpeciesIndex {
+    pub fn new(config: &SpeciesIndexConfig) -> Self {
+        Self {
+            refresh_interval: Duration::from_secs(config.refresh_interval_secs),
+            retry_interval: Duration::from_secs(config.retry_interval_secs),
+            names: RwLock::new(None),
+        }
+    }
+
+    /// Whether the name is certainly unknown.
+    ///
+    /// Until the index has been loaded, no name is rejected. IDs like `6` aren't indexed, they
+    /// are never rejected either.
+    pub fn rejects(&self, name: &str) -> bool {
+        if name.bytes().all(|b| b.is_ascii_digit()) {
+            return false;
+        }
+        matches!(&*self.names.read().unwrap(), Some(names) if !names.contains(name))
+    }
+
+    /// Fetch all names from the PokéAPI, replacing the previous index.
+    #[tracing::instrument(name = "Load species index", skip(self, poke_api))]
+    pub async fn load(&self, poke_api: &PokeApi) -> Result<usize, ApiError> {
+        let (species, varieties) =
+            futures::try_join!(poke_api.list_species(), poke_api.list_pokemon())?;
+        let names = species.into_iter().chain(varieties).collect::<HashSet<_>>();
+        let len = names.len();
+        *self.names.write().unwrap() = Some(names);
+        Ok(len)
+    }
+
+    /// Number of indexed names.
+    pub fn len(&self) -> usize {
+        self.names.read().unwrap().as_ref().map_or(0, HashSet::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Background task loading the index right away and refreshing it, sooner after a failure.
+///
+/// The server doesn't wait for the first load, names are looked up as before until then.
+pub async fn refresh(index: web::Data<SpeciesIndex>, poke_api: web::Data<PokeApi>) {
+    loop {
+        let interval = match index.load(&poke_api).await {
+            Ok(len) => {
+                tracing::info!("Loaded the species index with {} names", len);
+                index.refresh_interval
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load the species index: {}", e);
+                index.retry_interval
+            }
+        };
+        actix_web::rt::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::config::SpeciesIndexConfig;
+
+    use super::SpeciesIndex;
+
+    #[test]
+    fn test_rejects() {
+        let index = SpeciesIndex::new(&SpeciesIndexConfig {
+            refresh_interval_secs: 86400,
+            retry_interval_secs: 300,
+        });
+        // nothing is rejected until the index is loaded
+        assert!(!index.rejects("missingno"));
+
+        let names = ["charizard", "charizard-mega-x"];
+        *index.names.write().unwrap() = Some(names.iter().map(|n| n.to_string()).collect());
+        assert!(!index.rejects("charizard-mega-x"));
+        assert!(index.rejects("missingno"));
+        assert!(!index.rejects("6"));
+        assert_eq!(index.len(), 2);
+
+        *index.names.write().unwrap() = Some(HashSet::new());
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Cached roster of all species for picking random Pokémon.
+//!
+//! Listing all species costs a large upstream call, so the list is cached for a day. The
+//! legendary status is only part of the species themselves, the legendary species are thus
+//! found by scanning all species in the background once they are asked for. A failed scan is
+//! only retried after the configured interval, as every scan fetches all species.
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::web;
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use crate::api_clients::pokeapi::PokeApi;
+use crate::api_clients::ApiError;
+use crate::config::RandomConfig;
+
+/// Species lists are fetched again once they are this old.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Number of species fetched from the PokéAPI concurrently while scanning.
+const CONCURRENT_FETCHES: usize = 8;
+
+#[derive(Debug)]
+struct Cached<T> {
+    fetched: Instant,
+    value: Arc<T>,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T) -> Self {
+        Self {
+            fetched: Instant::now(),
+            value: Arc::new(value),
+        }
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.fetched.elapsed() < MAX_AGE
+    }
+}
+
+/// The names of all species and of the legendary ones among them.
+#[derive(Debug)]
+pub struct SpeciesRoster {
+    species: RwLock<Option<Cached<Vec<String>>>>,
+    legendary: RwLock<Option<Cached<HashSet<String>>>>,
+    scanning: AtomicBool,
+    /// When the last scan failed, if it did
+    failed: Mutex<Option<Instant>>,
+    retry_interval: Duration,
+}
+
+impl SpeciesRoster {
+    pub fn new(config: &RandomConfig) -> Self {
+        Self {
+            species: RwLock::new(None),
+            legendary: RwLock::new(None),
+            scanning: AtomicBool::new(false),
+            failed: Mutex::new(None),
+            retry_interval: Duration::from_secs(config.legendary_retry_interval_secs),
+        }
+    }
+
+    /// The names of all species, only fetched if the cached ones are missing or outdated.
+    pub async fn species(&self, poke_api: &PokeApi) -> Result<Arc<Vec<String>>, ApiError> {
+        if let Some(cached) = &*self.species.read().unwrap() {
+            if cached.is_fresh() {
+                return Ok(cached.value.clone());
+            }
+        }
+        let cached = Cached::new(poke_api.list_species().await?);
+        let species = cached.value.clone();
+        *self.species.write().unwrap() = Some(cached);
+        Ok(species)
+    }
+
+    /// The names of all legendary species, `None` until the first scan has completed.
+    ///
+    /// Outdated names are still returned while they are scanned again.
+    pub fn legendary(&self) -> Option<Arc<HashSet<String>>> {
+        self.legendary
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|cached| cached.value.clone())
+    }
+
+    /// Whether the legendary species have to be scanned, only `true` for a single caller until
+    /// the scan has finished and never before the retry interval has passed since a failed scan.
+    fn claim_scan(&self) -> bool {
+        let outdated =
+            !matches!(&*self.legendary.read().unwrap(), Some(cached) if cached.is_fresh());
+        let backing_off = matches!(
+            *self.failed.lock().unwrap(),
+            Some(failed) if failed.elapsed() < self.retry_interval
+        );
+        outdated
+            && !backing_off
+            && self
+                .scanning
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+    }
+
+    /// Fetch all species and keep the names of the legendary ones.
+    #[tracing::instrument(name = "Scan legendary species", skip(self, poke_api))]
+    async fn scan(&self, poke_api: &PokeApi) -> Result<usize, ApiError> {
+        let species = self.species(poke_api).await?;
+        let legendary = stream::iter(species.iter())
+            .map(|name| poke_api.get_pokemon_species_description(name))
+            .buffer_unordered(CONCURRENT_FETCHES)
+            .try_filter_map(|species| async move {
+                Ok(species.is_legendary().then(|| species.name().to_string()))
+            })
+            .try_collect::<HashSet<_>>()
+            .await?;
+        let len = legendary.len();
+        *self.legendary.write().unwrap() = Some(Cached::new(legendary));
+        Ok(len)
+    }
+}
+
+/// Start scanning the legendary species in the background, unless they are known already, being
+/// scanned or the last scan failed too recently.
+pub fn scan_legendary(roster: &web::Data<SpeciesRoster>, poke_api: &web::Data<PokeApi>) {
+    if !roster.claim_scan() {
+        return;
+    }
+    let roster = roster.clone();
+    let poke_api = poke_api.clone();
+    actix_web::rt::spawn(async move {
+        let failed = match roster.scan(&poke_api).await {
+            Ok(len) => {
+                tracing::info!("Found {} legendary species", len);
+                None
+            }
+            Err(e) => {
+                // the next request after the retry interval tries again
+                tracing::warn!("Failed to scan the legendary species: {}", e);
+                Some(Instant::now())
+            }
+        };
+        *roster.failed.lock().unwrap() = failed;
+        roster.scanning.store(false, Ordering::Release);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use std::time::Instant;
+
+    use crate::api_clients::pokeapi::PokeApi;
+    use crate::config::{PokeApiUrl, RandomConfig};
+
+    use super::SpeciesRoster;
+
+    static CHARIZARD_RESPONSE: &[u8] = include_bytes!("../testdata/charizard.json");
+
+    #[tokio::test]
+    async fn test_scan() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/{}", PokeApi::SPECIES)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": 2,
+                "results": [{ "name": "charizard" }, { "name": "mewtwo" }],
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        for (name, is_legendary) in &[("charizard", false), ("mewtwo", true)] {
+            let mut species: serde_json::Value =
+                serde_json::from_slice(CHARIZARD_RESPONSE).unwrap();
+            species["name"] = (*name).into();
+            species["is_legendary"] = (*is_legendary).into();
+            Mock::given(method("GET"))
+                .and(path(format!("/{}{}", PokeApi::SPECIES, name)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(species))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let api = PokeApi::new(PokeApiUrl(mock_server.uri().parse().unwrap()));
+        let roster = SpeciesRoster::new(&RandomConfig::default());
+        assert!(roster.legendary().is_none());
+        assert!(roster.claim_scan());
+        // a single scan at a time
+        assert!(!roster.claim_scan());
+
+        assert_eq!(roster.scan(&api).await.unwrap(), 1);
+        let legendary = roster.legendary().unwrap();
+        assert!(legendary.contains("mewtwo"));
+        assert!(!legendary.contains("charizard"));
+
+        // the species list is cached
+        assert_eq!(roster.species(&api).await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_retry_interval() {
+        let roster = SpeciesRoster::new(&RandomConfig::default());
+        *roster.failed.lock().unwrap() = Some(Instant::now());
+        // a failed scan isn't retried right away
+        assert!(!roster.claim_scan());
+
+        let roster = SpeciesRoster::new(&RandomConfig {
+            legendary_retry_interval_secs: 1,
+        });
+        *roster.failed.lock().unwrap() = Some(Instant::now() - std::time::Duration::from_secs(2));
+        assert!(roster.claim_scan());
+    }
+}
@@ -1,24 +1,116 @@
 // Based on https://github.com/LukeMathWalker/zero-to-production/blob/main/src/telemetry.rs
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::HeaderMap;
+use actix_web::Error;
+use futures::future::{ok, Ready};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self as sdktrace, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::trace::{TraceError, TracerProvider};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
 use tracing::subscriber::set_global_default;
-use tracing::Subscriber;
+use tracing::{Span, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 
+use crate::config::{LogConfig, LogFormat, LogRotation, OtlpConfig};
+use crate::request_id::{RequestId, REQUEST_ID_HEADER};
+
 /// Compose multiple layers into a `tracing`'s subscriber.
 ///
 /// # Implementation Notes
 ///
 /// We are using `impl Subscriber` as return type to avoid having to spell out the actual
 /// type of the returned subscriber, which is indeed quite complex.
-pub fn get_subscriber(name: String, default_env_filter: String) -> impl Subscriber + Sync + Send {
+///
+/// `RUST_LOG` takes precedence over the filters from the `LogConfig`.
+pub fn get_subscriber<Sink>(
+    name: String,
+    log: &LogConfig,
+    sink: Sink,
+    tracer: Tracer,
+) -> impl Subscriber + Sync + Send
+where
+    Sink: MakeWriter + Send + Sync + 'static,
+{
     let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, std::io::stdout);
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log.filter()));
+    // only one of the formatting layers is enabled, `Option<Layer>` is a no-op if `None`
+    let (json_layer, pretty_layer) = match log.format {
+        LogFormat::Json => (Some(BunyanFormattingLayer::new(name, sink)), None),
+        LogFormat::Pretty => (
+            None,
+            Some(tracing_subscriber::fmt::layer().pretty().with_writer(sink)),
+        ),
+    };
     Registry::default()
         .with(env_filter)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
         .with(JsonStorageLayer)
-        .with(formatting_layer)
+        .with(json_layer)
+        .with(pretty_layer)
+}
+
+/// Get the writer for our logs, either stdout or a rotating log file.
+///
+/// Log files are written on a background thread, the returned guard has to be held until
+/// exit to flush all pending logs.
+pub fn get_sink(log: &LogConfig) -> (BoxMakeWriter, Option<WorkerGuard>) {
+    match &log.file {
+        Some(file) => {
+            let rotation = match file.rotation {
+                LogRotation::Minutely => Rotation::MINUTELY,
+                LogRotation::Hourly => Rotation::HOURLY,
+                LogRotation::Daily => Rotation::DAILY,
+                LogRotation::Never => Rotation::NEVER,
+            };
+            let appender = RollingFileAppender::new(rotation, &file.directory, &file.prefix);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    }
+}
+
+/// Build the OpenTelemetry tracer backing our spans.
+///
+/// Spans are exported via OTLP over HTTP if configured. Without an exporter, the tracer still
+/// assigns trace IDs so that the W3C trace context is propagated to upstream calls.
+pub fn get_tracer(name: String, otlp: Option<&OtlpConfig>) -> Result<Tracer, TraceError> {
+    let trace_config =
+        sdktrace::config().with_resource(Resource::new(vec![KeyValue::new("service.name", name)]));
+    match otlp {
+        Some(otlp) => opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_endpoint(otlp.endpoint.as_str()),
+            )
+            .with_trace_config(trace_config)
+            .install_batch(opentelemetry::runtime::TokioCurrentThread),
+        None => {
+            let provider = sdktrace::TracerProvider::builder()
+                .with_config(trace_config)
+                .build();
+            let tracer = provider.tracer(env!("CARGO_PKG_NAME"), Some(env!("CARGO_PKG_VERSION")));
+            // the tracer only holds a weak reference, the provider has to be kept alive
+            global::set_tracer_provider(provider);
+            Ok(tracer)
+        }
+    }
 }
 
 /// Register a subscriber as global default to process span data.
@@ -26,5 +118,131 @@ pub fn get_subscriber(name: String, default_env_filter: String) -> impl Subscrib
 /// It should only be called once!
 pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) {
     LogTracer::init().expect("Failed to set logger");
+    global::set_text_map_propagator(TraceContextPropagator::new());
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
+
+/// Flush all spans which haven't been exported yet and stop the trace export.
+///
+/// Blocks until the export is done, spans recorded afterwards are dropped.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Inject the W3C trace context of the current span and the request ID into an outgoing request.
+pub(crate) fn propagate_context(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let mut headers = reqwest::header::HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(
+            &Span::current().context(),
+            &mut opentelemetry_http::HeaderInjector(&mut headers),
+        )
+    });
+    if let Some(value) =
+        RequestId::current().and_then(|id| reqwest::header::HeaderValue::from_str(id.as_str()).ok())
+    {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    request.headers(headers)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Middleware continuing the W3C trace context of incoming requests.
+///
+/// It has to be registered *inside* of `TracingLogger`, i.e. wrapped before it, so that the
+/// `traceparent` header becomes the parent of `TracingLogger`'s root span.
+pub struct TraceContext;
+
+impl<S, B> Transform<S, ServiceRequest> for TraceContext
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TraceContextMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(TraceContextMiddleware { service })
+    }
+}
+
+#[doc(hidden)]
+pub struct TraceContextMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for TraceContextMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(req.headers()))
+        });
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            // polled within the root span of `TracingLogger`
+            Span::current().set_parent(parent);
+            fut.await
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use opentelemetry::trace::{Span, Tracer};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::config::OtlpConfig;
+
+    use super::get_tracer;
+
+    #[tokio::test]
+    async fn test_otlp_export() {
+        let collector = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/traces"))
+            .and(header("content-type", "application/x-protobuf"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&collector)
+            .await;
+
+        let otlp = OtlpConfig {
+            endpoint: format!("{}/v1/traces", collector.uri()).parse().unwrap(),
+        };
+        let tracer = get_tracer("pokespeare-test".into(), Some(&otlp)).unwrap();
+        tracer.start("exported span").end();
+
+        // shutting down flushes all pending spans, it blocks until the export is done
+        tokio::task::spawn_blocking(opentelemetry::global::shutdown_tracer_provider)
+            .await
+            .unwrap();
+    }
+}
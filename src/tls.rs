@@ -0,0 +1,157 @@
+//! Native TLS support through rustls.
+//!
+//! The server certificate is served through a `CertificateResolver`, which is periodically
+//! checked for changes of the certificate and key files, e.g. after a renewal, so that new
+//! certificates are picked up without a restart.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey};
+use rustls::{
+    AllowAnyAuthenticatedClient, ClientHello, NoClientAuth, ProtocolVersion, ResolvesServerCert,
+    RootCertStore, ServerConfig,
+};
+
+use crate::config::{TlsConfig, TlsVersion};
+
+/// Possible errors while loading TLS certificates and keys.
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("No PEM encoded certificates found in {0}")]
+    NoCertificates(PathBuf),
+    #[error("No supported PEM encoded private key found in {0}")]
+    NoPrivateKey(PathBuf),
+    #[error("Invalid client CA certificate in {0}")]
+    InvalidClientCa(PathBuf),
+}
+
+/// Build the rustls `ServerConfig` for the given TLS settings.
+///
+/// The returned resolver serves the certificate and can be reloaded through
+/// `CertificateResolver::reload_if_changed`.
+pub fn server_config(
+    tls: &TlsConfig,
+) -> Result<(ServerConfig, Arc<CertificateResolver>), TlsError> {
+    let mut config = match &tls.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            let (valid, _) = roots
+                .add_pem_file(&mut open(path)?)
+                .map_err(|_| TlsError::InvalidClientCa(path.clone()))?;
+            if valid == 0 {
+                return Err(TlsError::InvalidClientCa(path.clone()));
+            }
+            ServerConfig::new(AllowAnyAuthenticatedClient::new(roots))
+        }
+        None => ServerConfig::new(NoClientAuth::new()),
+    };
+    config.versions = match tls.min_version {
+        TlsVersion::Tls12 => vec![ProtocolVersion::TLSv1_3, ProtocolVersion::TLSv1_2],
+        TlsVersion::Tls13 => vec![ProtocolVersion::TLSv1_3],
+    };
+    let resolver = Arc::new(CertificateResolver::load(&tls.cert_path, &tls.key_path)?);
+    config.cert_resolver = resolver.clone();
+    Ok((config, resolver))
+}
+
+/// Periodically reload the certificate until the surrounding runtime stops.
+pub async fn watch_certificate(resolver: Arc<CertificateResolver>, interval: Duration) {
+    let mut interval = actix_web::rt::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match resolver.reload_if_changed() {
+            Ok(true) => tracing::info!("Reloaded TLS certificate"),
+            Ok(false) => {}
+            // keep serving the previous certificate, e.g. if only one of the files was replaced
+            Err(e) => tracing::warn!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
+
+/// Serves the current certificate and reloads it when the files change.
+pub struct CertificateResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<LoadedCertificate>,
+}
+
+struct LoadedCertificate {
+    key: CertifiedKey,
+    modified: (Option<SystemTime>, Option<SystemTime>),
+}
+
+impl CertificateResolver {
+    /// Load the PEM encoded certificate chain and private key.
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Self, TlsError> {
+        let resolver = Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(load_certificate(cert_path, key_path)?),
+        };
+        Ok(resolver)
+    }
+
+    /// Reload the certificate if either file has been modified since the last load.
+    ///
+    /// Returns whether the certificate has been reloaded.
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        let modified = (modified(&self.cert_path), modified(&self.key_path));
+        if self.current.read().unwrap().modified == modified {
+            return Ok(false);
+        }
+        let loaded = load_certificate(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap() = loaded;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<CertifiedKey> {
+        Some(self.current.read().unwrap().key.clone())
+    }
+}
+
+fn load_certificate(cert_path: &Path, key_path: &Path) -> Result<LoadedCertificate, TlsError> {
+    // the modification times are read first, a change while loading triggers another reload
+    let modified = (modified(cert_path), modified(key_path));
+
+    let certs = pemfile::certs(&mut open(cert_path)?)
+        .ok()
+        .filter(|certs| !certs.is_empty())
+        .ok_or_else(|| TlsError::NoCertificates(cert_path.to_path_buf()))?;
+
+    let no_key = || TlsError::NoPrivateKey(key_path.to_path_buf());
+    let mut keys = pemfile::pkcs8_private_keys(&mut open(key_path)?).map_err(|_| no_key())?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut open(key_path)?).map_err(|_| no_key())?;
+    }
+    let key = keys.into_iter().next().ok_or_else(no_key)?;
+    let signing_key = sign::any_supported_type(&key).map_err(|_| no_key())?;
+
+    Ok(LoadedCertificate {
+        key: CertifiedKey::new(certs, Arc::new(signing_key)),
+        modified,
+    })
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, TlsError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| TlsError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,329 @@
+//! In-memory cache of translations.
+//!
+//! The same flavor text always receives the same translation, so a cached translation can be
+//! served instead of spending the scarce translation quota again. Optionally, the cache is
+//! saved to a file and loaded again by the next instance.
+use std::collections::{BTreeSet, HashMap};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::metrics;
+
+/// The least recently used translation is evicted once this many are cached, pinned ones are
+/// kept regardless.
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug)]
+struct Entry {
+    translation: String,
+    inserted: Instant,
+    last_used: Instant,
+    hits: u64,
+    /// Inserted by an operator, never evicted or replaced by a regular insertion
+    pinned: bool,
+    /// The species described by the text, e.g. `charizard`
+    species: BTreeSet<String>,
+}
+
+impl Entry {
+    fn snapshot(&self, text: &str) -> CachedTranslation {
+        CachedTranslation {
+            text: text.to_string(),
+            translation: self.translation.clone(),
+            age: self.inserted.elapsed(),
+            hits: self.hits,
+            pinned: self.pinned,
+            species: self.species.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Snapshot of a cached translation, e.g. for inspection by operators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedTranslation {
+    pub text: String,
+    pub translation: String,
+    /// Time since the translation was cached
+    pub age: Duration,
+    pub hits: u64,
+    pub pinned: bool,
+    /// The species described by the text, if it is a species description
+    pub species: Vec<String>,
+}
+
+/// A cached translation as saved to the file of a persistent cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedTranslation {
+    text: String,
+    translation: String,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    species: Vec<String>,
+}
+
+/// Translations by their original text.
+#[derive(Debug, Default)]
+pub struct TranslationCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    /// The file the cache is saved to, guarded to not interleave concurrent saves
+    path: Option<Mutex<PathBuf>>,
+}
+
+impl TranslationCache {
+    /// Load the cache saved to a file, starting empty if the file doesn't exist yet.
+    ///
+    /// The cache is saved to the same file by `flush`.
+    pub fn open(path: PathBuf) -> anyhow::Result<Self> {
+        let persisted: Vec<PersistedTranslation> = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Invalid translation cache {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read translation cache {}", path.display())
+                })
+            }
+        };
+        let cache = Self {
+            entries: Mutex::default(),
+            path: Some(Mutex::new(path)),
+        };
+        for persisted in persisted {
+            cache.store(&persisted.text, persisted.translation, persisted.pinned);
+            for species in &persisted.species {
+                cache.add_species(&persisted.text, species);
+            }
+        }
+        Ok(cache)
+    }
+
+    /// Save all translations to the file the cache was opened from, if any.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path.lock().unwrap(),
+            None => return Ok(()),
+        };
+        let mut persisted = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(text, entry)| PersistedTranslation {
+                text: text.clone(),
+                translation: entry.translation.clone(),
+                pinned: entry.pinned,
+                species: entry.species.iter().cloned().collect(),
+            })
+            .collect::<Vec<_>>();
+        persisted.sort_by(|a, b| a.text.cmp(&b.text));
+        // replaced at once, a crash while writing never leaves a truncated cache behind
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&persisted)?)
+            .with_context(|| format!("Failed to write translation cache {}", tmp.display()))?;
+        std::fs::rename(&tmp, &*path)
+            .with_context(|| format!("Failed to replace translation cache {}", path.display()))
+    }
+
+    /// Look up the translation of a text, counting a hit if it is cached.
+    pub fn get(&self, text: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let translation = entries.get_mut(text).map(|entry| {
+            entry.hits += 1;
+            entry.last_used = Instant::now();
+            entry.translation.clone()
+        });
+        metrics::observe_cache_lookup("translation", translation.is_some());
+        translation
+    }
+
+    /// Whether the translation of a text is cached, without counting a hit.
+    pub fn contains(&self, text: &str) -> bool {
+        self.entries.lock().unwrap().contains_key(text)
+    }
+
+    /// Cache the translation of a text, replacing any previous one unless it is pinned.
+    pub fn insert(&self, text: &str, translation: String) {
+        self.store(text, translation, false);
+    }
+
+    /// Cache a translation which is neither evicted nor replaced by `insert`, e.g. a correction.
+    pub fn pin(&self, text: &str, translation: String) -> CachedTranslation {
+        self.store(text, translation, true)
+            .expect("Pinned translations are always stored")
+    }
+
+    /// Store a translation, returns a snapshot of it unless a pinned one is kept instead.
+    fn store(&self, text: &str, translation: String, pinned: bool) -> Option<CachedTranslation> {
+        let mut entries = self.entries.lock().unwrap();
+        let species = match entries.get_mut(text) {
+            Some(entry) if entry.pinned && !pinned => return None,
+            Some(entry) => std::mem::take(&mut entry.species),
+            None => BTreeSet::new(),
+        };
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(text) {
+            let least_recently_used = entries
+                .iter()
+                .filter(|(_, entry)| !entry.pinned)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(text, _)| text.clone());
+            if let Some(text) = least_recently_used {
+                entries.remove(&text);
+            }
+        }
+        let now = Instant::now();
+        let entry = Entry {
+            translation,
+            inserted: now,
+            last_used: now,
+            hits: 0,
+            pinned,
+            species,
+        };
+        let snapshot = entry.snapshot(text);
+        entries.insert(text.to_string(), entry);
+        Some(snapshot)
+    }
+
+    /// Record a species described by a cached text, e.g. to list the cached species.
+    pub fn add_species(&self, text: &str, species: &str) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(text) {
+            if !entry.species.contains(species) {
+                entry.species.insert(species.to_string());
+            }
+        }
+    }
+
+    /// Remove the translation of a text, returns whether it was cached.
+    pub fn remove(&self, text: &str) -> bool {
+        self.entries.lock().unwrap().remove(text).is_some()
+    }
+
+    /// Remove all translations, returns how many were cached.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let len = entries.len();
+        entries.clear();
+        len
+    }
+
+    /// Snapshot of all cached translations, the oldest first.
+    pub fn entries(&self) -> Vec<CachedTranslation> {
+        let entries = self.entries.lock().unwrap();
+        // sorted by the insertion time itself, the ages of the snapshots are taken one by one
+        let mut sorted = entries.iter().collect::<Vec<_>>();
+        sorted.sort_by(|(a_text, a), (b_text, b)| {
+            a.inserted.cmp(&b.inserted).then_with(|| a_text.cmp(b_text))
+        });
+        sorted
+            .into_iter()
+            .map(|(text, entry)| entry.snapshot(text))
+            .collect()
+    }
+
+    /// Number of cached translations.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{TranslationCache, MAX_ENTRIES};
+
+    #[test]
+    fn test_persistence() {
+        let path =
+            std::env::temp_dir().join(format!("pokespeare-cache-{}.json", uuid::Uuid::new_v4()));
+        // a missing file is created by the first flush
+        let cache = TranslationCache::open(path.clone()).unwrap();
+        assert!(cache.is_empty());
+        cache.insert("that", "yond".into());
+        cache.add_species("that", "charizard");
+        cache.pin("is", "is".into());
+        cache.flush().unwrap();
+
+        let reopened = TranslationCache::open(path.clone()).unwrap();
+        assert_eq!(reopened.get("that").as_deref(), Some("yond"));
+        let pinned = reopened
+            .entries()
+            .into_iter()
+            .map(|e| (e.text, e.pinned))
+            .collect::<HashMap<_, _>>();
+        assert!(!pinned["that"]);
+        assert!(pinned["is"]);
+        let species = reopened.entries().into_iter().map(|e| e.species);
+        assert!(species.eq(vec![vec![], vec!["charizard".to_string()]]));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(TranslationCache::open(path.clone()).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        // caches without a file aren't saved anywhere
+        assert!(TranslationCache::default().flush().is_ok());
+    }
+
+    #[test]
+    fn test_translation_cache() {
+        let cache = TranslationCache::default();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get("that"), None);
+        assert!(!cache.contains("that"));
+
+        cache.insert("that", "yond".into());
+        assert_eq!(cache.get("that").as_deref(), Some("yond"));
+        assert!(cache.contains("that"));
+        assert_eq!(cache.entries.lock().unwrap()["that"].hits, 1);
+
+        // corrections replace the previous translation, the described species are kept
+        cache.add_species("that", "charizard");
+        cache.add_species("missingno", "missingno");
+        cache.insert("that", "yonder".into());
+        assert_eq!(cache.get("that").as_deref(), Some("yonder"));
+        assert_eq!(cache.len(), 1);
+
+        cache.insert("is", "is".into());
+        let entries = cache.entries();
+        let texts = entries.iter().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["that", "is"]);
+        assert_eq!(entries[0].translation, "yonder");
+        assert_eq!(entries[0].hits, 1);
+        assert_eq!(entries[0].species, ["charizard"]);
+        assert!(entries[1].species.is_empty());
+
+        assert!(cache.remove("that"));
+        assert!(!cache.remove("that"));
+        assert_eq!(cache.clear(), 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_pinned() {
+        let cache = TranslationCache::default();
+        cache.pin("that", "yonder".into());
+        // corrections aren't replaced by regular translations
+        cache.insert("that", "yond".into());
+        assert_eq!(cache.get("that").as_deref(), Some("yonder"));
+        cache.pin("that", "yond".into());
+        assert_eq!(cache.get("that").as_deref(), Some("yond"));
+
+        // nor evicted once the cache is full
+        for i in 0..MAX_ENTRIES {
+            cache.insert(&i.to_string(), i.to_string());
+        }
+        assert_eq!(cache.len(), MAX_ENTRIES);
+        assert!(cache.contains("that"));
+        assert!(!cache.contains("0"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Warming of the translation cache with the descriptions of popular species.
+//!
+//! The translation quota only allows a few translations per hour, so a fresh instance would
+//! answer most requests with 429 for quite a while. The warming task pre-translates the flavor
+//! texts of a list of species in the background, spreading the translations over time and
+//! pausing while the quota runs low, so that requests still get a share of it.
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::web;
+use anyhow::Context;
+
+use crate::api_clients::pokeapi::{FlavorTexts, PokeApi, PokemonSpeciesResponse};
+use crate::api_clients::shakespeare::TranslationApi;
+use crate::api_clients::ApiError;
+use crate::config::{Config, WarmConfig};
+use crate::translation_cache::TranslationCache;
+
+/// Lower bound between retries of rate limited translations, even with a shorter interval.
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Translations of the quota left to requests, warming pauses once no more are remaining.
+const QUOTA_RESERVE: u64 = 2;
+
+/// Read the species names of a file with one name per line, skipping blank and `#` lines.
+pub fn read_species_file(path: &Path) -> std::io::Result<Vec<String>> {
+    Ok(parse_species_list(&std::fs::read_to_string(path)?))
+}
+
+fn parse_species_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Order the distinct English flavor texts of all species, one text of each species per round,
+/// along with the name of their species.
+///
+/// Every species thus has a cached description early on, even if the quota only suffices for a
+/// fraction of all texts.
+fn schedule(species: &[PokemonSpeciesResponse]) -> Vec<(&str, &str)> {
+    let texts = species
+        .iter()
+        .map(|species| {
+            species
+                .english_flavor_text_entries()
+                .map(|entry| (species.name(), entry.flavor_text()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let rounds = texts.iter().map(Vec::len).max().unwrap_or(0);
+    let mut seen = HashSet::new();
+    (0..rounds)
+        .flat_map(|round| texts.iter().filter_map(move |texts| texts.get(round)))
+        .copied()
+        .filter(|(_, text)| seen.insert(*text))
+        .collect()
+}
+
+/// Background task pre-translating the flavor texts of the species into the translation cache.
+///
+/// Translations are at least `interval` apart and wait for the quota to recover once it's
+/// exhausted or down to the reserve for requests. The task ends once all texts are cached.
+pub async fn warm(
+    names: Vec<String>,
+    interval: Duration,
+    poke_api: web::Data<PokeApi>,
+    translate_api: web::Data<TranslationApi>,
+    translation_cache: web::Data<TranslationCache>,
+) {
+    let mut species = Vec::with_capacity(names.len());
+    for name in &names {
+        match poke_api.resolve_species(name).await {
+            Ok(resp) => species.push(resp),
+            Err(e) => tracing::warn!("Failed to prefetch {} for warming: {}", name, e),
+        }
+    }
+
+    let texts = schedule(&species);
+    tracing::info!(
+        "Warming the translation cache with {} flavor texts of {} species",
+        texts.len(),
+        species.len()
+    );
+    let mut warmed = 0;
+    let mut translated_before = false;
+    for (species, text) in texts {
+        if translation_cache.contains(text) {
+            translation_cache.add_species(text, species);
+            continue;
+        }
+        if translated_before {
+            actix_web::rt::time::sleep(interval).await;
+        }
+        translated_before = true;
+        loop {
+            if let Some(exhausted_for) = translate_api.quota_exhausted_for() {
+                actix_web::rt::time::sleep(exhausted_for).await;
+            }
+            if let Some(remaining) = translate_api
+                .quota_remaining()
+                .filter(|remaining| *remaining <= QUOTA_RESERVE)
+            {
+                tracing::debug!(
+                    "Leaving the remaining {} translations to requests",
+                    remaining
+                );
+                actix_web::rt::time::sleep(interval.max(MIN_RETRY_INTERVAL)).await;
+                continue;
+            }
+            match translate_api.translate(text).await {
+                Ok(translation) => {
+                    translation_cache.insert(text, translation);
+                    translation_cache.add_species(text, species);
+                    warmed += 1;
+                    // an interrupted warm-up doesn't lose any translations
+                    if let Err(e) = translation_cache.flush() {
+                        tracing::warn!("Failed to save the translation cache: {:?}", e);
+                    }
+                }
+                // retried once the quota recovers, at the latest after the interval
+                Err(ApiError::RateLimit(e)) => {
+                    tracing::debug!("Translation quota exhausted while warming: {}", e);
+                    actix_web::rt::time::sleep(interval.max(MIN_RETRY_INTERVAL)).await;
+                    continue;
+                }
+                Err(e) => tracing::warn!("Failed to translate a flavor text for warming: {}", e),
+            }
+            break;
+        }
+    }
+    tracing::info!("Warmed the translation cache with {} translations", warmed);
+}
+
+/// Warm the persistent translation cache of the config without serving any requests.
+///
+/// Resolves once all texts are cached, the next instance then starts with the cached
+/// translations.
+pub async fn warm_persisted(config: &Config, species_file: &Path) -> anyhow::Result<()> {
+    let cache = config
+        .translation_cache
+        .as_ref()
+        .context("Warming requires the `translation_cache` section to save the translations")?;
+    let names = read_species_file(species_file)
+        .with_context(|| format!("Failed to read species file {}", species_file.display()))?;
+    let interval = config
+        .warm
+        .as_ref()
+        .map_or_else(WarmConfig::default_interval_secs, |warm| warm.interval_secs);
+    let translation_cache = web::Data::new(TranslationCache::open(cache.path.clone())?);
+    warm(
+        names,
+        Duration::from_secs(interval),
+        web::Data::new(PokeApi::from_config(config)?),
+        web::Data::new(TranslationApi::new(config.translator_api_base_url.clone())),
+        translation_cache.clone(),
+    )
+    .await;
+    translation_cache.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::api_clients::pokeapi::{FlavorTexts, PokemonSpeciesResponse};
+
+    use super::{parse_species_list, schedule};
+
+    static CHARIZARD_RESPONSE: &[u8] = include_bytes!("../testdata/charizard.json");
+    static PIKACHU_RESPONSE: &[u8] = include_bytes!("../testdata/pikachu.json");
+
+    #[test]
+    fn test_parse_species_list() {
+        let names = parse_species_list("# top species\ncharizard\n\n  Pikachu \n");
+        assert_eq!(names, ["charizard", "pikachu"]);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let species = [CHARIZARD_RESPONSE, PIKACHU_RESPONSE]
+            .iter()
+            .map(|resp| serde_json::from_slice::<PokemonSpeciesResponse>(resp).unwrap())
+            .collect::<Vec<_>>();
+        let texts = schedule(&species);
+
+        // species take turns
+        let charizard = species[0].english_flavor_text_entries().next().unwrap();
+        let pikachu = species[1].english_flavor_text_entries().next().unwrap();
+        assert_eq!(
+            texts[..2],
+            [
+                ("charizard", charizard.flavor_text()),
+                ("pikachu", pikachu.flavor_text())
+            ]
+        );
+
+        // identical texts of different games are only translated once
+        let mut distinct = texts.iter().map(|(_, text)| text).collect::<Vec<_>>();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), texts.len());
+    }
+}
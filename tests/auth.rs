@@ -0,0 +1,362 @@
+mod setup;
+
+use pokespearify::config::{ApiKeyConfig, AuthConfig, TranslationCacheConfig};
+use pokespearify::translation_cache::TranslationCache;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use setup::TestApp;
+
+fn key(name: &str) -> ApiKeyConfig {
+    ApiKeyConfig {
+        name: name.into(),
+        key: format!("{}-secret", name),
+        disabled: false,
+        requests_per_hour: None,
+        translations_per_day: None,
+        admin: false,
+    }
+}
+
+async fn get(app: &TestApp, path: &str, key: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new().get(format!("http://{}{}", app.inner().addr(), path));
+    if let Some(key) = key {
+        request = request.header("X-Api-Key", key);
+    }
+    request.send().await.expect("The service is not working")
+}
+
+#[actix_rt::test]
+async fn test_api_key_required() {
+    let app = TestApp::spawn_with(|config| {
+        config.auth = Some(AuthConfig {
+            keys: vec![
+                key("alice"),
+                ApiKeyConfig {
+                    disabled: true,
+                    ..key("mallory")
+                },
+            ],
+            keys_file: None,
+        })
+    })
+    .await;
+    app.with_poke_api(1).await.with_translate_api(1).await;
+
+    for key in &[None, Some("guessed")] {
+        let resp = get(&app, "/pokemon/charizard", *key).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers()["content-type"], "application/problem+json");
+        let problem = resp
+            .json::<Problem>()
+            .await
+            .expect("Got an invalid response");
+        assert_eq!(problem.status, 401);
+    }
+
+    let resp = get(&app, "/pokemon/charizard", Some("mallory-secret")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = get(&app, "/pokemon/charizard", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // health checks and metrics don't require a key
+    for path in &["/healthz", "/metrics"] {
+        assert_eq!(get(&app, path, None).await.status(), StatusCode::OK);
+    }
+}
+
+#[actix_rt::test]
+async fn test_request_quota() {
+    let app = TestApp::spawn_with(|config| {
+        config.auth = Some(AuthConfig {
+            keys: vec![
+                ApiKeyConfig {
+                    requests_per_hour: Some(1),
+                    ..key("alice")
+                },
+                key("bob"),
+            ],
+            keys_file: None,
+        })
+    })
+    .await;
+    // bob is served from the translation cache
+    app.with_poke_api(2).await.with_translate_api(1).await;
+
+    let resp = get(&app, "/pokemon/charizard", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = get(&app, "/pokemon/charizard", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = resp.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 3600);
+    let problem = resp
+        .json::<Problem>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(problem.detail, "Request quota exceeded, try again later.");
+
+    // quotas are tracked per key
+    let resp = get(&app, "/pokemon/charizard", Some("bob-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_translation_budget() {
+    let app = TestApp::spawn_with(|config| {
+        config.auth = Some(AuthConfig {
+            keys: vec![ApiKeyConfig {
+                translations_per_day: Some(1),
+                ..key("alice")
+            }],
+            keys_file: None,
+        })
+    })
+    .await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/move/thunderbolt"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/thunderbolt.json")[..],
+            "application/json",
+        ))
+        .mount(app.mock_poke_api())
+        .await;
+    // titles always count against the budget and never reach the translation API here
+    let title =
+        "Charizard, the Flame Pokémon, that spits fire that is hot enough to melt boulders.";
+    app.with_translation(title, "Charizard, the Flame Pokémon, yond spits fire.", 0)
+        .await;
+    app.with_poke_api(2).await.with_translate_api(1).await;
+
+    // the title exceeds the budget and is left out
+    let resp = get(&app, "/pokemon/charizard?title=true", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp.json::<serde_json::Value>().await.unwrap();
+    assert!(resp.get("title").is_none());
+
+    // cached translations don't count against the budget, titles on top of them still do
+    let resp = get(&app, "/pokemon/charizard?title=true", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp.json::<serde_json::Value>().await.unwrap();
+    assert!(resp.get("title").is_none());
+
+    let resp = get(&app, "/move/thunderbolt", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("retry-after"));
+    let problem = resp
+        .json::<Problem>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(
+        problem.detail,
+        "Translation budget exceeded, try again later."
+    );
+}
+
+#[actix_rt::test]
+async fn test_keys_file() {
+    let path = std::env::temp_dir().join(format!("pokespeare-keys-{}.yml", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        "keys:\n  - name: carol\n    key: carol-secret\n    requests_per_hour: 10\n",
+    )
+    .unwrap();
+
+    let keys_file = path.clone();
+    let app = TestApp::spawn_with(|config| {
+        config.auth = Some(AuthConfig {
+            keys: vec![key("alice")],
+            keys_file: Some(keys_file),
+        })
+    })
+    .await;
+    // carol is served from the translation cache
+    app.with_poke_api(2).await.with_translate_api(1).await;
+
+    for key in &["alice-secret", "carol-secret"] {
+        let resp = get(&app, "/pokemon/charizard", Some(key)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Problem {
+    status: u16,
+    detail: String,
+}
+
+#[actix_rt::test]
+async fn test_admin_cache() {
+    let cache_path =
+        std::env::temp_dir().join(format!("pokespeare-cache-{}.json", uuid::Uuid::new_v4()));
+    let app = TestApp::spawn_with(|config| {
+        config.auth = Some(AuthConfig {
+            keys: vec![
+                ApiKeyConfig {
+                    admin: true,
+                    ..key("alice")
+                },
+                key("bob"),
+            ],
+            keys_file: None,
+        });
+        config.translation_cache = Some(TranslationCacheConfig {
+            path: cache_path.clone(),
+        });
+    })
+    .await;
+    app.with_poke_api(2).await.with_translate_api(1).await;
+    let admin = |method: reqwest::Method, path: &str, key: &str| {
+        reqwest::Client::new()
+            .request(method, format!("http://{}{}", app.inner().addr(), path))
+            .header("X-Api-Key", key)
+    };
+
+    let resp = get(&app, "/pokemon/charizard", Some("bob-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = get(&app, "/admin/cache", Some("bob-secret")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = get(&app, "/admin/cache", Some("alice-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listing = resp
+        .json::<CacheListing>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(listing.species, ["charizard"]);
+    assert_eq!(listing.entries.len(), 1);
+    let entry = &listing.entries[0];
+    assert_eq!(entry.hits, 0);
+    assert!(!entry.pinned);
+    assert_eq!(entry.species, ["charizard"]);
+    assert_eq!(
+        entry.translation,
+        TestApp::charizard_translation_response().translation()
+    );
+
+    // corrections are served right away and pinned
+    let resp = admin(reqwest::Method::PUT, "/admin/cache", "alice-secret")
+        .json(&serde_json::json!({
+            "text": entry.text,
+            "translation": "Spits fire yond is hot enow to melt boulders.",
+        }))
+        .send()
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let corrected = resp
+        .json::<CacheEntry>()
+        .await
+        .expect("Got an invalid response");
+    assert!(corrected.pinned);
+    // the species described by the corrected text are kept
+    assert_eq!(corrected.species, ["charizard"]);
+    // and the correction is saved right away
+    let saved = TranslationCache::open(cache_path.clone()).unwrap();
+    assert_eq!(
+        saved.get(&entry.text).as_deref(),
+        Some("Spits fire yond is hot enow to melt boulders.")
+    );
+    let resp = get(&app, "/pokemon/charizard", Some("bob-secret")).await;
+    let description = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(
+        description["description"],
+        "Spits fire yond is hot enow to melt boulders."
+    );
+
+    let path = format!("/admin/cache/{}", entry.id);
+    let resp = admin(reqwest::Method::DELETE, &path, "alice-secret")
+        .send()
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = admin(reqwest::Method::DELETE, &path, "alice-secret")
+        .send()
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = admin(reqwest::Method::PUT, "/admin/cache", "alice-secret")
+        .json(&serde_json::json!({ "text": "that", "translation": "yond" }))
+        .send()
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = admin(reqwest::Method::DELETE, "/admin/cache", "alice-secret")
+        .send()
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let listing = get(&app, "/admin/cache", Some("alice-secret"))
+        .await
+        .json::<CacheListing>()
+        .await
+        .expect("Got an invalid response");
+    assert!(listing.species.is_empty());
+    assert!(listing.entries.is_empty());
+    assert!(TranslationCache::open(cache_path.clone())
+        .unwrap()
+        .is_empty());
+    std::fs::remove_file(&cache_path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_admin_cache_unsaved() {
+    // the directory of the cache file doesn't exist
+    let cache_path = std::env::temp_dir()
+        .join(format!("pokespeare-{}", uuid::Uuid::new_v4()))
+        .join("cache.json");
+    let app = TestApp::spawn_with(|config| {
+        config.auth = Some(AuthConfig {
+            keys: vec![ApiKeyConfig {
+                admin: true,
+                ..key("alice")
+            }],
+            keys_file: None,
+        });
+        config.translation_cache = Some(TranslationCacheConfig { path: cache_path });
+    })
+    .await;
+
+    let resp = reqwest::Client::new()
+        .put(format!("http://{}/admin/cache", app.inner().addr()))
+        .header("X-Api-Key", "alice-secret")
+        .json(&serde_json::json!({ "text": "that", "translation": "yond" }))
+        .send()
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let problem = resp
+        .json::<Problem>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(problem.detail, "Failed to save the translation cache.");
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CacheListing {
+    species: Vec<String>,
+    entries: Vec<CacheEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CacheEntry {
+    id: String,
+    text: String,
+    translation: String,
+    hits: u64,
+    pinned: bool,
+    species: Vec<String>,
+}
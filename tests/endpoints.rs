@@ -1,7 +1,18 @@
 mod setup;
 
+use std::path::Path;
+use std::time::Duration;
+
+use pokespearify::config::{
+    AuthConfig, CorsConfig, DailyConfig, DatasetConfig, DatasetFormat, RateLimitConfig,
+    SpeciesIndexConfig, TranslationCacheConfig, WarmConfig,
+};
+use pokespearify::translation_cache::TranslationCache;
+use pokespearify::warming;
 use reqwest::StatusCode;
 use serde::Deserialize;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
 use setup::TestApp;
 
@@ -16,14 +27,8 @@ async fn test_health() {
 
 #[actix_rt::test]
 async fn test_pokemon() {
-    #[derive(Deserialize, Debug)]
-    struct ShakespearedDescriptionResponse {
-        name: String,
-        description: String,
-    }
-
     let app = TestApp::spawn().await;
-    app.with_poke_api(2).await.with_translate_api(2).await;
+    app.with_poke_api(2).await.with_translate_api(1).await;
 
     let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
         .await
@@ -55,6 +60,1056 @@ async fn test_pokemon() {
     assert_eq!(resp.description, desc.translation());
 }
 
+#[actix_rt::test]
+async fn test_title() {
+    let app = TestApp::spawn().await;
+    app.with_translation(
+        "Charizard, the Flame Pokémon, that spits fire that is hot enough to melt boulders.",
+        "Charizard, the Flame Pokémon, yond spits fire yond is hot enow to melt boulders.",
+        1,
+    )
+    .await;
+    app.with_poke_api(1).await.with_translate_api(1).await;
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard?title=true",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(
+        resp["title"],
+        "Charizard, the Flame Pokémon, yond spits fire yond is hot enow to melt boulders."
+    );
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp["description"], desc.translation());
+}
+
+#[actix_rt::test]
+async fn test_title_rate_limited() {
+    let app = TestApp::spawn().await;
+    app.with_translation_error(
+        "Charizard, the Flame Pokémon, that spits fire that is hot enough to melt boulders.",
+        429,
+        1,
+    )
+    .await;
+    app.with_poke_api(1).await.with_translate_api(1).await;
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard?title=true",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The pokemon endpoint is not working");
+    // the description is still returned, just without a title
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert!(resp.get("title").is_none());
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp["description"], desc.translation());
+}
+
+#[actix_rt::test]
+async fn test_move_and_ability() {
+    let app = TestApp::spawn().await;
+    for (resource, body) in &[
+        (
+            "api/v2/move/thunderbolt",
+            &include_bytes!("../testdata/thunderbolt.json")[..],
+        ),
+        (
+            "api/v2/ability/blaze",
+            &include_bytes!("../testdata/blaze.json")[..],
+        ),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(*resource))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(*body, "application/json"))
+            .mount(app.mock_poke_api())
+            .await;
+    }
+    app.with_translation(
+        "A strong electric blast crashes down on the target. This may also leave the target with paralysis.",
+        "A strong electric blast crashes down on the target. This may eke leave the target with paralysis.",
+        1,
+    )
+    .await
+    .with_translation(
+        "Powers up Fire-type moves in a pinch.",
+        "Powers up fire-type moves in a pinch, forsooth.",
+        1,
+    )
+    .await;
+
+    let resp = reqwest::get(format!(
+        "http://{}/move/thunderbolt?version=x-y",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The move endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().contains_key("etag"));
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.name, "thunderbolt");
+    assert_eq!(
+        resp.description,
+        "A strong electric blast crashes down on the target. This may eke leave the target with paralysis."
+    );
+
+    let resp = reqwest::get(format!("http://{}/ability/blaze", app.inner().addr()))
+        .await
+        .expect("The ability endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.name, "blaze");
+    assert_eq!(
+        resp.description,
+        "Powers up fire-type moves in a pinch, forsooth."
+    );
+
+    let resp = reqwest::get(format!("http://{}/move/splashier", app.inner().addr()))
+        .await
+        .expect("The move endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.detail, "Unknown move.");
+}
+
+#[actix_rt::test]
+async fn test_item_and_berry() {
+    let app = TestApp::spawn().await;
+    for (resource, body) in &[
+        (
+            "api/v2/berry/oran",
+            &include_bytes!("../testdata/oran.json")[..],
+        ),
+        (
+            "api/v2/item/oran-berry",
+            &include_bytes!("../testdata/oran_berry.json")[..],
+        ),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(*resource))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(*body, "application/json"))
+            .mount(app.mock_poke_api())
+            .await;
+    }
+    app.with_translation(
+        "If held by a Pokémon, it heals the user by just 10 HP.",
+        "If did hold by a Pokémon, it heals the user by just 10 hp.",
+        1,
+    )
+    .await;
+
+    for resource in &["item/oran-berry", "berry/oran"] {
+        let resp = reqwest::get(format!(
+            "http://{}/{}?version=x-y",
+            app.inner().addr(),
+            resource
+        ))
+        .await
+        .expect("The item endpoint is not working");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = resp
+            .json::<ShakespearedDescriptionResponse>()
+            .await
+            .expect("Got an invalid response");
+        assert_eq!(
+            resp.description,
+            "If did hold by a Pokémon, it heals the user by just 10 hp."
+        );
+    }
+
+    let resp = reqwest::get(format!("http://{}/berry/missingno", app.inner().addr()))
+        .await
+        .expect("The berry endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.detail, "Unknown berry.");
+}
+
+#[actix_rt::test]
+async fn test_info() {
+    let app = TestApp::spawn().await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/missingno"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(app.mock_poke_api())
+        .await;
+    // nothing is translated
+    app.with_poke_api(1).await.with_translate_api(0).await;
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard/info",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The info endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().contains_key("etag"));
+
+    let info = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(info["id"], 6);
+    assert_eq!(info["display_name"], "Charizard");
+    assert_eq!(info["genus"], "Flame Pokémon");
+    assert_eq!(info["names"]["fr"], "Dracaufeu");
+    assert_eq!(info["generation"], "generation-i");
+    assert_eq!(info["habitat"], "mountain");
+    assert_eq!(info["is_legendary"], false);
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/missingno/info",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The info endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_evolutions() {
+    let app = TestApp::spawn().await;
+    app.with_evolution_chain().await;
+    // every stage is a Charizard, its description is only translated once
+    app.with_poke_api(6).await.with_translate_api(1).await;
+
+    for _ in 0..2 {
+        let resp = reqwest::get(format!(
+            "http://{}/pokemon/charmeleon/evolutions",
+            app.inner().addr()
+        ))
+        .await
+        .expect("The evolutions endpoint is not working");
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = resp
+            .json::<serde_json::Value>()
+            .await
+            .expect("Got an invalid response");
+        assert_eq!(resp["chain_id"], 2);
+        let desc = TestApp::charizard_translation_response();
+        let stages = resp["stages"].as_array().unwrap();
+        let stages = stages
+            .iter()
+            .map(|stage| {
+                assert_eq!(stage["status"], 200);
+                assert_eq!(stage["description"], desc.translation());
+                (
+                    stage["name"].as_str().unwrap(),
+                    stage["stage"].as_u64().unwrap(),
+                    stage["evolves_from"].as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            [
+                ("charmander", 1, None),
+                ("charmeleon", 2, Some("charmander")),
+                ("charizard", 3, Some("charmeleon")),
+            ]
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn test_varieties() {
+    let app = TestApp::spawn().await;
+    for name in &["charizard-mega-x", "missingno"] {
+        Mock::given(method("GET"))
+            .and(path(format!("api/v2/pokemon-species/{}", name)))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(app.mock_poke_api())
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon/charizard-mega-x"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": 10034,
+            "name": "charizard-mega-x",
+            "is_default": false,
+            "species": { "name": "charizard", "url": "https://pokeapi.co/api/v2/pokemon-species/6/" },
+            "forms": [{ "name": "charizard-mega-x", "url": "https://pokeapi.co/api/v2/pokemon-form/10076/" }]
+        })))
+        .expect(4)
+        .mount(app.mock_poke_api())
+        .await;
+    app.with_evolution_chain().await;
+    // the batch and the evolutions are served from the translation cache
+    app.with_poke_api(6).await.with_translate_api(1).await;
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard-mega-x",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.name, "charizard-mega-x");
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp.description, desc.translation());
+
+    // neither a species nor a variety
+    let resp = reqwest::get(format!("http://{}/pokemon/missingno", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // batches resolve varieties the same way
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/pokemon/batch", app.inner().addr()))
+        .json(&serde_json::json!({ "names": ["charizard-mega-x", "missingno"] }))
+        .send()
+        .await
+        .expect("The batch endpoint is not working");
+    let resp = resp
+        .json::<BatchResponse>()
+        .await
+        .expect("Got an invalid response");
+    let statuses = resp
+        .results
+        .iter()
+        .map(|item| (item.name.as_str(), item.status))
+        .collect::<Vec<_>>();
+    assert_eq!(statuses, [("charizard-mega-x", 200), ("missingno", 404)]);
+    assert_eq!(
+        resp.results[0].description.as_deref(),
+        Some(desc.translation())
+    );
+
+    // so do the species metadata and evolutions
+    for route in &["info", "evolutions"] {
+        let resp = reqwest::get(format!(
+            "http://{}/pokemon/charizard-mega-x/{}",
+            app.inner().addr(),
+            route
+        ))
+        .await
+        .expect("The pokemon endpoint is not working");
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
+
+#[actix_rt::test]
+async fn test_conditional_requests() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(4).await.with_translate_api(1).await;
+    let url = format!("http://{}/pokemon/charizard", app.inner().addr());
+
+    // fixing the version makes the description deterministic and cacheable
+    let resp = reqwest::get(format!("{}?version=red", url))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["cache-control"], "public, max-age=86400");
+    let etag = resp.headers()["etag"].clone();
+    assert!(etag.to_str().unwrap().starts_with('"'));
+
+    let resp = reqwest::Client::new()
+        .get(format!("{}?version=red", url))
+        .header("If-None-Match", etag.clone())
+        .send()
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers()["etag"], etag);
+    assert!(resp.bytes().await.unwrap().is_empty());
+
+    // random descriptions must not be cached
+    let resp = reqwest::get(&url)
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["cache-control"], "no-store");
+    assert!(!resp.headers().contains_key("etag"));
+
+    let resp = reqwest::get(format!("{}?version=blue", url))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_content_negotiation() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(3).await.with_translate_api(1).await;
+    let desc = TestApp::charizard_translation_response();
+
+    let get = |accept: &'static str| {
+        reqwest::Client::new()
+            .get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+            .header("Accept", accept)
+            .send()
+    };
+
+    let resp = get("text/plain")
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/plain; charset=utf-8");
+    assert_eq!(resp.text().await.unwrap(), desc.translation());
+
+    let resp = get("text/html,application/xhtml+xml,*/*;q=0.8")
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    assert!(resp.text().await.unwrap().contains("<h1>charizard</h1>"));
+
+    let resp = get("image/png")
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+
+    // responses are compressed on request
+    let resp = reqwest::Client::new()
+        .get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .header("Accept-Encoding", "br, gzip")
+        .send()
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    assert_eq!(resp.headers()["content-encoding"], "br");
+}
+
+#[actix_rt::test]
+async fn test_batch() {
+    let app = TestApp::spawn().await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/missingno"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(app.mock_poke_api())
+        .await;
+    // species and translations are shared within the batch
+    app.with_poke_api(2).await.with_translate_api(1).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/pokemon/batch", app.inner().addr()))
+        .json(&serde_json::json!({
+            "names": ["charizard", "pikachu", "charizard", "missingno"]
+        }))
+        .send()
+        .await
+        .expect("The batch endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = resp
+        .json::<BatchResponse>()
+        .await
+        .expect("Got an invalid response");
+    let desc = TestApp::charizard_translation_response();
+    let statuses = resp
+        .results
+        .iter()
+        .map(|item| (item.name.as_str(), item.status))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [
+            ("charizard", 200),
+            ("pikachu", 200),
+            ("charizard", 200),
+            ("missingno", 404)
+        ]
+    );
+    assert_eq!(
+        resp.results[1].description.as_deref(),
+        Some(desc.translation())
+    );
+    assert!(resp.results[3].description.is_none());
+}
+
+#[actix_rt::test]
+async fn test_batch_rate_limit() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(2).await.with_translate_rate_limit().await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/pokemon/batch", app.inner().addr()))
+        .json(&serde_json::json!({ "names": ["charizard", "pikachu"] }))
+        .send()
+        .await
+        .expect("The batch endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<BatchResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert!(resp.results.iter().all(|item| item.status == 429));
+
+    // invalid batches are rejected as a whole
+    for body in &[serde_json::json!({ "names": [] }), serde_json::json!({})] {
+        let resp = reqwest::Client::new()
+            .post(format!("http://{}/pokemon/batch", app.inner().addr()))
+            .json(body)
+            .send()
+            .await
+            .expect("The batch endpoint is not working");
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = resp
+            .json::<ErrorResponse>()
+            .await
+            .expect("Got an invalid response");
+        assert_eq!(resp.status, 400);
+    }
+}
+
+#[actix_rt::test]
+async fn test_batch_order() {
+    let app = TestApp::spawn().await;
+    for name in &["squirtle", "bulbasaur", "pikachu"] {
+        let mut species: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/charizard_single_text.json"))
+                .unwrap();
+        species["name"] = (*name).into();
+        species["flavor_text_entries"][0]["flavor_text"] = format!("About {}.", name).into();
+        Mock::given(method("GET"))
+            .and(path(format!("api/v2/pokemon-species/{}", name)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(species))
+            .expect(1)
+            .mount(app.mock_poke_api())
+            .await;
+    }
+    // the quota runs out on the second name, the later ones aren't translated anymore
+    app.with_translation("About squirtle.", "Anent squirtle.", 1)
+        .await
+        .with_translation_error("About bulbasaur.", 429, 1)
+        .await
+        .with_translation("About pikachu.", "Anent pikachu.", 0)
+        .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/pokemon/batch", app.inner().addr()))
+        .json(&serde_json::json!({
+            "names": ["squirtle", "bulbasaur", "squirtle", "pikachu"]
+        }))
+        .send()
+        .await
+        .expect("The batch endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<BatchResponse>()
+        .await
+        .expect("Got an invalid response");
+    let statuses = resp
+        .results
+        .iter()
+        .map(|item| (item.name.as_str(), item.status))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [
+            ("squirtle", 200),
+            ("bulbasaur", 429),
+            ("squirtle", 200),
+            ("pikachu", 429)
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn test_random() {
+    let app = TestApp::spawn().await;
+    let names = ["bulbasaur", "charmander", "squirtle"];
+    app.with_species_index(&names).await;
+    // every species is a non-legendary Charizard, all of them are fetched once to find the
+    // legendary ones
+    app.with_poke_api(5).await.with_translate_api(1).await;
+    let species_fetches = || async {
+        app.mock_poke_api()
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| {
+                let path = request.url.path();
+                path.starts_with("/api/v2/pokemon-species/") && path != "/api/v2/pokemon-species/"
+            })
+            .count()
+    };
+
+    for query in &["", "?generation=generation-i&legendary=false"] {
+        let resp = reqwest::get(format!(
+            "http://{}/pokemon/random{}",
+            app.inner().addr(),
+            query
+        ))
+        .await
+        .expect("The random endpoint is not working");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["cache-control"], "no-store");
+
+        let resp = resp
+            .json::<ShakespearedDescriptionResponse>()
+            .await
+            .expect("Got an invalid response");
+        assert!(names.contains(&resp.name.as_str()));
+        let desc = TestApp::charizard_translation_response();
+        assert_eq!(resp.description, desc.translation());
+    }
+
+    // the legendary filter started a scan of all species in the background
+    for _ in 0..50 {
+        if species_fetches().await == 5 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    // none of the species is legendary, which is known without fetching any of them again
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/random?legendary=true",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The random endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(species_fetches().await, 5);
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/random?habitat=atlantis",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The random endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.detail, "Unknown generation or habitat.");
+}
+
+#[actix_rt::test]
+async fn test_daily() {
+    let app = TestApp::spawn_with(|config| {
+        config.daily = Some(DailyConfig {
+            timezone: chrono_tz::Tz::Europe__Berlin,
+            retry_interval_secs: 1,
+        });
+    })
+    .await;
+    let names = ["bulbasaur", "charmander", "squirtle"];
+    app.with_species_index(&names).await;
+    // today and tomorrow are prepared in the background, requests never reach the upstream APIs
+    // and the description of both days is only translated once
+    app.with_poke_api(2).await.with_translate_api(1).await;
+
+    let url = format!("http://{}/pokemon/daily", app.inner().addr());
+    let mut resp = reqwest::get(&url)
+        .await
+        .expect("The daily endpoint is not working");
+    for _ in 0..50 {
+        if resp.status() != StatusCode::SERVICE_UNAVAILABLE {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+        resp = reqwest::get(&url)
+            .await
+            .expect("The daily endpoint is not working");
+    }
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cache_control = resp.headers()["cache-control"].to_str().unwrap();
+    assert!(cache_control.starts_with("public, max-age="));
+    let etag = resp.headers()["etag"].clone();
+
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert!(names.contains(&resp.name.as_str()));
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp.description, desc.translation());
+
+    // the same Pokémon is served all day
+    let resp = reqwest::get(&url)
+        .await
+        .expect("The daily endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["etag"], etag);
+
+    // wait for tomorrow to be prepared ahead of midnight
+    for _ in 0..50 {
+        let requests = app.mock_poke_api().received_requests().await.unwrap();
+        let species_fetches = requests
+            .iter()
+            .filter(|request| request.url.path() != "/api/v2/pokemon-species/")
+            .count();
+        if species_fetches == 2 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[actix_rt::test]
+async fn test_daily_disabled() {
+    let app = TestApp::spawn().await;
+
+    let resp = reqwest::get(format!("http://{}/pokemon/daily", app.inner().addr()))
+        .await
+        .expect("The daily endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.detail, "The Pokémon of the day is not enabled.");
+}
+
+#[actix_rt::test]
+async fn test_offline_dataset() {
+    let app = TestApp::spawn_with(|config| {
+        config.dataset = Some(DatasetConfig {
+            path: "testdata/dataset/csv".into(),
+            format: DatasetFormat::Csv,
+        })
+    })
+    .await;
+    app.with_translate_api(1).await;
+
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard-mega-x?version=red",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp.description, desc.translation());
+
+    // resources besides species aren't part of the dataset
+    let resp = reqwest::get(format!("http://{}/move/thunderbolt", app.inner().addr()))
+        .await
+        .expect("The move endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let requests = app.mock_poke_api().received_requests().await.unwrap();
+    assert!(requests.is_empty());
+}
+
+#[actix_rt::test]
+async fn test_species_index() {
+    // the index is loaded right away, before the mocks of the `TestApp` could be mounted
+    let mock_poke_api = MockServer::start().await;
+    for (resource, name) in &[
+        ("api/v2/pokemon-species/", "charizard"),
+        ("api/v2/pokemon/", "charizard-mega-x"),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(*resource))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": 1,
+                "results": [{ "name": name }],
+            })))
+            .expect(1)
+            .mount(&mock_poke_api)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/charizard"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard_single_text.json")[..],
+            "application/json",
+        ))
+        .expect(1)
+        .mount(&mock_poke_api)
+        .await;
+    // IDs aren't part of the index
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/6"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard_single_text.json")[..],
+            "application/json",
+        ))
+        .expect(1)
+        .mount(&mock_poke_api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/missingno"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(0)
+        .mount(&mock_poke_api)
+        .await;
+
+    let app = TestApp::spawn_with_upstreams(mock_poke_api, MockServer::start().await, |config| {
+        config.species_index = Some(SpeciesIndexConfig {
+            refresh_interval_secs: 86400,
+            retry_interval_secs: 300,
+        })
+    })
+    .await;
+    app.with_translate_api(1).await;
+    // the server doesn't wait for the index
+    for _ in 0..50 {
+        let requests = app.mock_poke_api().received_requests().await.unwrap();
+        if requests.len() == 2 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = reqwest::get(format!("http://{}/pokemon/6", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = reqwest::get(format!("http://{}/pokemon/missingno", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.detail, "Unknown Pokémon.");
+
+    // batches are checked against the index as well
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/pokemon/batch", app.inner().addr()))
+        .json(&serde_json::json!({ "names": ["missingno"] }))
+        .send()
+        .await
+        .expect("The batch endpoint is not working");
+    let resp = resp
+        .json::<BatchResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.results[0].status, 404);
+
+    // so are the species metadata and evolutions
+    for route in &["info", "evolutions"] {
+        let resp = reqwest::get(format!(
+            "http://{}/pokemon/missingno/{}",
+            app.inner().addr(),
+            route
+        ))
+        .await
+        .expect("The pokemon endpoint is not working");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+#[actix_rt::test]
+async fn test_warming() {
+    // warming starts along with the application, before the mocks of the `TestApp` could be mounted
+    let mock_poke_api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/charizard"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard_single_text.json")[..],
+            "application/json",
+        ))
+        .mount(&mock_poke_api)
+        .await;
+    let mock_translate_api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("translate/shakespeare.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard_single_translation.json")[..],
+            "application/json",
+        ))
+        .expect(1)
+        .mount(&mock_translate_api)
+        .await;
+
+    let app = TestApp::spawn_with_upstreams(mock_poke_api, mock_translate_api, |config| {
+        config.warm = Some(WarmConfig {
+            species_file: "testdata/top_species.txt".into(),
+            interval_secs: 0,
+        })
+    })
+    .await;
+
+    let mut attempts = 0;
+    while app
+        .mock_translate_api()
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty()
+    {
+        attempts += 1;
+        assert!(attempts < 50, "The translation cache was not warmed");
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    // the translation is cached right after its response
+    actix_rt::time::sleep(Duration::from_millis(100)).await;
+
+    // served without another translation
+    let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp.description, desc.translation());
+}
+
+#[actix_rt::test]
+async fn test_warm_persisted() {
+    let mock_poke_api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/charizard"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard_single_text.json")[..],
+            "application/json",
+        ))
+        .mount(&mock_poke_api)
+        .await;
+    let mock_translate_api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("translate/shakespeare.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard_single_translation.json")[..],
+            "application/json",
+        ))
+        .expect(1)
+        .mount(&mock_translate_api)
+        .await;
+    let cache_path =
+        std::env::temp_dir().join(format!("pokespeare-cache-{}.json", uuid::Uuid::new_v4()));
+    let cache = TranslationCacheConfig {
+        path: cache_path.clone(),
+    };
+
+    // warming without serving returns once all texts are cached
+    let mut config = TestApp::config(&mock_poke_api, &mock_translate_api);
+    config.translation_cache = Some(cache.clone());
+    warming::warm_persisted(&config, Path::new("testdata/top_species.txt"))
+        .await
+        .expect("Failed to warm the translation cache");
+
+    // the next instance starts with the warmed cache
+    let app = TestApp::spawn_with(|config| config.translation_cache = Some(cache)).await;
+    app.with_poke_api(1).await.with_translate_api(0).await;
+    let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = resp
+        .json::<ShakespearedDescriptionResponse>()
+        .await
+        .expect("Got an invalid response");
+    let desc = TestApp::charizard_translation_response();
+    assert_eq!(resp.description, desc.translation());
+    std::fs::remove_file(&cache_path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_warming_reserve() {
+    let mock_poke_api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("api/v2/pokemon-species/charizard"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            &include_bytes!("../testdata/charizard.json")[..],
+            "application/json",
+        ))
+        .mount(&mock_poke_api)
+        .await;
+    // the reserve is reached right after the first translation
+    let mock_translate_api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("translate/shakespeare.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(
+                    &include_bytes!("../testdata/charizard_single_translation.json")[..],
+                    "application/json",
+                )
+                .insert_header("X-RateLimit-Remaining", "2"),
+        )
+        .expect(2)
+        .mount(&mock_translate_api)
+        .await;
+
+    let app = TestApp::spawn_with_upstreams(mock_poke_api, mock_translate_api, |config| {
+        config.warm = Some(WarmConfig {
+            species_file: "testdata/top_species.txt".into(),
+            interval_secs: 0,
+        })
+    })
+    .await;
+    let translations = || async {
+        app.mock_translate_api()
+            .received_requests()
+            .await
+            .unwrap()
+            .len()
+    };
+    let mut attempts = 0;
+    while translations().await == 0 {
+        attempts += 1;
+        assert!(attempts < 50, "The translation cache was not warmed");
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    // warming pauses instead of spending the remaining quota
+    actix_rt::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(translations().await, 1);
+
+    // the first text is warmed, the text of yellow is left to this request
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard?version=yellow",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(translations().await, 2);
+}
+
+#[actix_rt::test]
+async fn test_admin_without_auth() {
+    let app = TestApp::spawn().await;
+
+    // the admin endpoints are closed unless an admin key is configured
+    let resp = reqwest::get(format!("http://{}/admin/cache", app.inner().addr()))
+        .await
+        .expect("The admin endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
 #[actix_rt::test]
 async fn test_rate_limit() {
     let app = TestApp::spawn().await;
@@ -65,11 +1120,390 @@ async fn test_rate_limit() {
         .expect("The pokemon endpoint is not working");
     assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
 
+    let request_id = resp
+        .headers()
+        .get("x-request-id")
+        .expect("The response has no request ID")
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(resp.headers()["content-type"], "application/problem+json");
     let resp = resp
-        .json::<String>()
+        .json::<ErrorResponse>()
         .await
         .expect("Got an invalid response");
-    assert_eq!(resp, "Too many requests, try again later.");
+    assert_eq!(resp.status, 429);
+    assert_eq!(resp.detail, "Too many requests, try again later.");
+    assert_eq!(resp.request_id, request_id);
+}
+
+#[actix_rt::test]
+async fn test_client_rate_limit() {
+    let app = TestApp::spawn_with(|config| {
+        config.rate_limit = Some(RateLimitConfig {
+            burst: 1,
+            per_minute: 1,
+            trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+        })
+    })
+    .await;
+    app.with_poke_api(2).await.with_translate_api(1).await;
+
+    let get = |client: &'static str| {
+        reqwest::Client::new()
+            .get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+            .header("X-Forwarded-For", client)
+            .send()
+    };
+
+    let resp = get("198.51.100.1")
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["ratelimit-limit"], "1");
+    assert_eq!(resp.headers()["ratelimit-remaining"], "0");
+    assert_eq!(resp.headers()["ratelimit-reset"], "60");
+
+    let resp = get("198.51.100.1")
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers()["retry-after"], "60");
+    assert_eq!(resp.headers()["ratelimit-remaining"], "0");
+
+    // clients behind the trusted proxy are limited separately
+    let resp = get("198.51.100.2")
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // other endpoints aren't limited
+    let resp = reqwest::get(format!("http://{}/healthz", app.inner().addr()))
+        .await
+        .expect("The healthcheck endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!resp.headers().contains_key("ratelimit-limit"));
+}
+
+#[actix_rt::test]
+async fn test_cors_preflight() {
+    let app = TestApp::spawn_with(|config| {
+        config.cors = Some(CorsConfig {
+            allowed_origins: vec!["https://pokedex.example.com".into()],
+            allowed_methods: vec!["GET".into()],
+            allowed_headers: vec!["x-api-key".into()],
+            max_age_secs: 600,
+        });
+        // preflight requests don't carry credentials
+        config.auth = Some(AuthConfig {
+            keys: Vec::new(),
+            keys_file: None,
+        });
+    })
+    .await;
+
+    let preflight = |origin: &str, method: &str| {
+        reqwest::Client::new()
+            .request(
+                reqwest::Method::OPTIONS,
+                format!("http://{}/pokemon/charizard", app.inner().addr()),
+            )
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", method)
+            .header("Access-Control-Request-Headers", "x-api-key")
+            .send()
+    };
+
+    let resp = preflight("https://pokedex.example.com", "GET")
+        .await
+        .expect("The preflight request failed");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let headers = resp.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://pokedex.example.com"
+    );
+    assert_eq!(headers["access-control-allow-methods"], "GET");
+    assert_eq!(headers["access-control-allow-headers"], "x-api-key");
+    assert_eq!(headers["access-control-max-age"], "600");
+
+    // unknown origins and methods are rejected
+    for (origin, method) in &[
+        ("https://evil.example.com", "GET"),
+        ("https://pokedex.example.com", "DELETE"),
+    ] {
+        let resp = preflight(origin, method)
+            .await
+            .expect("The preflight request failed");
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(!resp.headers().contains_key("access-control-allow-origin"));
+    }
+
+    // actual requests can read our headers, even on errors
+    let resp = reqwest::Client::new()
+        .get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .header("Origin", "https://pokedex.example.com")
+        .send()
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        resp.headers()["access-control-allow-origin"],
+        "https://pokedex.example.com"
+    );
+    let exposed = resp.headers()["access-control-expose-headers"]
+        .to_str()
+        .unwrap();
+    assert!(exposed.contains("x-request-id"));
+}
+
+#[actix_rt::test]
+async fn test_request_id() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(1).await.with_translate_api(1).await;
+
+    let resp = reqwest::Client::new()
+        .get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .header("X-Request-Id", "support-ticket-42")
+        .send()
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["x-request-id"], "support-ticket-42");
+
+    // the ID is forwarded to the upstream APIs
+    for mock in &[app.mock_poke_api(), app.mock_translate_api()] {
+        let requests = mock.received_requests().await.unwrap();
+        let request_id = requests[0]
+            .headers
+            .get(&"x-request-id".into())
+            .expect("The request ID was not forwarded");
+        assert_eq!(request_id.as_str(), "support-ticket-42");
+    }
+
+    // unknown routes get an ID and error body as well
+    let resp = reqwest::get(format!("http://{}/unknown", app.inner().addr()))
+        .await
+        .expect("The service is not working");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let request_id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.request_id, request_id);
+
+    // so do malformed queries
+    let resp = reqwest::get(format!(
+        "http://{}/pokemon/charizard?seed=abc",
+        app.inner().addr()
+    ))
+    .await
+    .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(resp.headers()["content-type"], "application/problem+json");
+    let request_id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    let resp = resp
+        .json::<ErrorResponse>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp.status, 400);
+    assert!(resp.detail.starts_with("Invalid query string"));
+    assert_eq!(resp.request_id, request_id);
+}
+
+#[actix_rt::test]
+async fn test_readiness() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api_root().await;
+
+    let resp = reqwest::get(format!("http://{}/readyz", app.inner().addr()))
+        .await
+        .expect("The readiness endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp["ready"], true);
+    assert_eq!(resp["dependencies"]["poke_api"]["up"], true);
+    assert_eq!(resp["dependencies"]["translation_api"]["up"], true);
+}
+
+#[actix_rt::test]
+async fn test_readiness_poke_api_down() {
+    let app = TestApp::spawn().await;
+
+    let resp = reqwest::get(format!("http://{}/readyz", app.inner().addr()))
+        .await
+        .expect("The readiness endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let resp = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp["ready"], false);
+    assert_eq!(resp["dependencies"]["poke_api"]["up"], false);
+    assert_eq!(resp["dependencies"]["translation_api"]["up"], true);
+
+    // the health check is unaffected by upstream failures
+    let resp = reqwest::get(format!("http://{}/healthz", app.inner().addr()))
+        .await
+        .expect("The healthcheck endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_readiness_quota_exhausted() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api_root()
+        .await
+        .with_poke_api(1)
+        .await
+        .with_translate_rate_limit()
+        .await;
+
+    let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let resp = reqwest::get(format!("http://{}/readyz", app.inner().addr()))
+        .await
+        .expect("The readiness endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let resp = resp
+        .json::<serde_json::Value>()
+        .await
+        .expect("Got an invalid response");
+    assert_eq!(resp["dependencies"]["poke_api"]["up"], true);
+    assert_eq!(resp["dependencies"]["translation_api"]["up"], false);
+}
+
+#[actix_rt::test]
+async fn test_metrics() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(1).await.with_translate_api(1).await;
+
+    let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = reqwest::get(format!("http://{}/metrics", app.inner().addr()))
+        .await
+        .expect("The metrics endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = resp.text().await.expect("Got an invalid response");
+    assert!(body.contains(
+        r#"http_requests_total{method="GET",route="/pokemon/{pokemon_name}",status="200"}"#
+    ));
+    assert!(body.contains("http_request_duration_seconds_bucket"));
+    assert!(body.contains(r#"upstream_requests_total{client="PokeApi"}"#));
+    assert!(body.contains(r#"upstream_requests_total{client="TranslationApi"}"#));
+    assert!(body.contains("upstream_request_duration_seconds_bucket"));
+}
+
+#[actix_rt::test]
+async fn test_metrics_quota_reset() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(1).await;
+    Mock::given(method("POST"))
+        .and(path("translate/shakespeare.json"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .mount(app.mock_translate_api())
+        .await;
+
+    let resp = reqwest::get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let scrape = || async {
+        reqwest::get(format!("http://{}/metrics", app.inner().addr()))
+            .await
+            .expect("The metrics endpoint is not working")
+            .text()
+            .await
+            .expect("Got an invalid response")
+    };
+    assert!(scrape().await.contains("translation_quota_remaining 0"));
+
+    // the exhaustion isn't reported anymore once the quota has reset
+    actix_rt::time::sleep(Duration::from_millis(1100)).await;
+    assert!(!scrape().await.contains("translation_quota_remaining"));
+}
+
+#[actix_rt::test]
+async fn test_trace_context_propagation() {
+    let app = TestApp::spawn().await;
+    app.with_poke_api(1).await.with_translate_api(1).await;
+
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let resp = reqwest::Client::new()
+        .get(format!("http://{}/pokemon/charizard", app.inner().addr()))
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", trace_id),
+        )
+        .send()
+        .await
+        .expect("The pokemon endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    for mock in &[app.mock_poke_api(), app.mock_translate_api()] {
+        let requests = mock.received_requests().await.unwrap();
+        let traceparent = requests[0]
+            .headers
+            .get(&"traceparent".into())
+            .expect("The trace context was not propagated");
+        assert!(traceparent.as_str().contains(trace_id));
+        // the upstream call has its own span in our trace
+        assert!(!traceparent.as_str().contains("00f067aa0ba902b7"));
+    }
+}
+
+#[actix_rt::test]
+async fn test_graceful_shutdown() {
+    let cache_path =
+        std::env::temp_dir().join(format!("pokespeare-cache-{}.json", uuid::Uuid::new_v4()));
+    let app = TestApp::spawn_with(|config| {
+        config.translation_cache = Some(TranslationCacheConfig {
+            path: cache_path.clone(),
+        })
+    })
+    .await;
+    app.with_slow_poke_api(Duration::from_millis(500))
+        .await
+        .with_translate_api(1)
+        .await;
+
+    let addr = app.inner().addr();
+    let in_flight = tokio::spawn(reqwest::get(format!("http://{}/pokemon/charizard", addr)));
+    actix_rt::time::sleep(Duration::from_millis(100)).await;
+
+    app.inner().shutdown_handle().shutdown().await;
+
+    // the in-flight request is drained before the server stops
+    let resp = in_flight
+        .await
+        .unwrap()
+        .expect("The in-flight request was dropped");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // new connections are refused
+    reqwest::get(format!("http://{}/healthz", addr))
+        .await
+        .expect_err("The server is still accepting connections");
+
+    // the translation of the drained request is saved
+    let cache = TranslationCache::open(cache_path.clone()).unwrap();
+    assert_eq!(cache.len(), 1);
+    std::fs::remove_file(&cache_path).unwrap();
 }
 
 #[derive(Deserialize, Debug)]
@@ -77,3 +1511,22 @@ pub struct ShakespearedDescriptionResponse {
     name: String,
     description: String,
 }
+
+#[derive(Deserialize, Debug)]
+pub struct BatchResponse {
+    results: Vec<BatchItemResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BatchItemResponse {
+    name: String,
+    status: u16,
+    description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorResponse {
+    status: u16,
+    detail: String,
+    request_id: String,
+}
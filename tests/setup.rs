@@ -1,8 +1,10 @@
 #![allow(dead_code)]
+use std::time::Duration;
+
 use lazy_static::lazy_static;
 use pokespearify::api_clients::shakespeare::{TranslationRequest, TranslationResponse};
-use pokespearify::config::{PokeApiUrl, TranslateApiUrl};
-use pokespearify::telemetry::{get_subscriber, init_subscriber};
+use pokespearify::config::{Config, LogConfig, PokeApiUrl, TranslateApiUrl};
+use pokespearify::telemetry::{get_subscriber, get_tracer, init_subscriber};
 
 use pokespearify::Application;
 use wiremock::matchers::{any, body_json, method, path, path_regex};
@@ -10,14 +12,22 @@ use wiremock::{Mock, MockServer, ResponseTemplate};
 
 lazy_static! {
     static ref LOG: () = {
-        let subscriber = get_subscriber("pokespeare-test-subscriber".into(), "error".into());
-        init_subscriber(subscriber);
+        let name = "pokespeare-test-subscriber".to_string();
+        let tracer = get_tracer(name.clone(), None).expect("Failed to create tracer");
+        // spans need to be enabled for trace context propagation, only print them on request
+        let log = LogConfig::default();
+        if std::env::var("TEST_LOG").is_ok() {
+            init_subscriber(get_subscriber(name, &log, std::io::stdout, tracer));
+        } else {
+            init_subscriber(get_subscriber(name, &log, std::io::sink, tracer));
+        }
     };
 }
 
-static SINGLE_CHARIZARD_RESPONSE: &'static [u8] =
-    include_bytes!("../testdata/charizard_single_text.json");
-static CHARIZARD_TRANSLATED_RESPONSE: &'static [u8] =
+static SINGLE_CHARIZARD_RESPONSE: &[u8] = include_bytes!("../testdata/charizard_single_text.json");
+static CHARIZARD_EVOLUTION_CHAIN_RESPONSE: &[u8] =
+    include_bytes!("../testdata/charizard_evolution_chain.json");
+static CHARIZARD_TRANSLATED_RESPONSE: &[u8] =
     include_bytes!("../testdata/charizard_single_translation.json");
 
 pub struct TestApp {
@@ -31,15 +41,30 @@ impl TestApp {
     ///
     /// The TestApp does not initialize any Mock Servers.
     pub async fn spawn() -> TestApp {
-        let _log = *LOG;
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Spawn the TestApp after customizing its `Config`.
+    pub async fn spawn_with(customize: impl FnOnce(&mut Config)) -> TestApp {
+        Self::spawn_with_upstreams(
+            MockServer::start().await,
+            MockServer::start().await,
+            customize,
+        )
+        .await
+    }
 
-        let mock_poke_api = MockServer::start().await;
-        let mock_translate_api = MockServer::start().await;
-        let poke_api_url = PokeApiUrl(mock_poke_api.uri().parse().unwrap());
-        let translate_api_url = TranslateApiUrl(mock_translate_api.uri().parse().unwrap());
-        let addr = ("127.0.0.1", 0);
+    /// Spawn the TestApp with mock servers prepared up front, e.g. for background tasks started
+    /// along with the `Application`.
+    pub async fn spawn_with_upstreams(
+        mock_poke_api: MockServer,
+        mock_translate_api: MockServer,
+        customize: impl FnOnce(&mut Config),
+    ) -> TestApp {
+        let mut config = Self::config(&mock_poke_api, &mock_translate_api);
+        customize(&mut config);
         TestApp {
-            inner: Application::new(addr, poke_api_url, translate_api_url)
+            inner: Application::new(&config)
                 .await
                 .expect("Failed to start test Application"),
             mock_poke_api,
@@ -47,11 +72,33 @@ impl TestApp {
         }
     }
 
+    /// The `Config` of a TestApp using the given mock servers, e.g. for running commands.
+    pub fn config(mock_poke_api: &MockServer, mock_translate_api: &MockServer) -> Config {
+        lazy_static::initialize(&LOG);
+
+        let mut config = Config::collect().expect("Failed to collect config");
+        config.host = "127.0.0.1".into();
+        config.port = 0;
+        config.poke_api_base_url = PokeApiUrl(mock_poke_api.uri().parse().unwrap());
+        config.translator_api_base_url = TranslateApiUrl(mock_translate_api.uri().parse().unwrap());
+        config
+    }
+
     /// Get a reference to the underlying `Application`.
     pub fn inner(&self) -> &Application {
         &self.inner
     }
 
+    /// Get a reference to the mocked PokéApi.
+    pub fn mock_poke_api(&self) -> &MockServer {
+        &self.mock_poke_api
+    }
+
+    /// Get a reference to the mocked translation API.
+    pub fn mock_translate_api(&self) -> &MockServer {
+        &self.mock_translate_api
+    }
+
     /// Mock the PokéApi by returning the same charizard description for all Pokémon queries.
     pub async fn with_poke_api(&self, expect: u64) -> &Self {
         Mock::given(method("GET"))
@@ -71,6 +118,9 @@ impl TestApp {
     }
 
     /// Mock the PokéApi by returning the same charizard description for all Pokémon queries.
+    ///
+    /// Repeated descriptions are served from the translation cache, so `expect` counts the
+    /// translations of an app rather than its requests.
     pub async fn with_translate_api(&self, expect: u64) -> &Self {
         Mock::given(method("POST"))
             .and(path("translate/shakespeare.json")) // it's all Charizard for testing purposes
@@ -87,6 +137,103 @@ impl TestApp {
         self
     }
 
+    /// Mock the translation of a single text, e.g. of a move or ability.
+    ///
+    /// Has to be mounted before `with_translate_api`, which answers all other texts with 404.
+    pub async fn with_translation(&self, text: &str, translation: &str, expect: u64) -> &Self {
+        Mock::given(method("POST"))
+            .and(path("translate/shakespeare.json"))
+            .and(body_json(TranslationRequest::from(text)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "success": { "total": 1 },
+                "contents": {
+                    "translated": translation,
+                    "text": text,
+                    "translation": "shakespeare"
+                }
+            })))
+            .expect(expect)
+            .mount(&self.mock_translate_api)
+            .await;
+        self
+    }
+
+    /// Mock a failed translation of a single text, like `with_translation`.
+    pub async fn with_translation_error(&self, text: &str, status: u16, expect: u64) -> &Self {
+        Mock::given(method("POST"))
+            .and(path("translate/shakespeare.json"))
+            .and(body_json(TranslationRequest::from(text)))
+            .respond_with(ResponseTemplate::new(status))
+            .expect(expect)
+            .mount(&self.mock_translate_api)
+            .await;
+        self
+    }
+
+    /// Mock the PokéApi like `with_poke_api` but delay each response.
+    pub async fn with_slow_poke_api(&self, delay: Duration) -> &Self {
+        Mock::given(method("GET"))
+            .and(path_regex(r"api/v2/pokemon-species/([a-zA-Z]|-)+"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(SINGLE_CHARIZARD_RESPONSE, "application/json")
+                    .set_delay(delay),
+            )
+            .mount(&self.mock_poke_api)
+            .await;
+        self
+    }
+
+    /// Mock the species index of the PokéApi and the first generation, both listing `names`.
+    ///
+    /// Has to be mounted before `with_poke_api`, which answers all other requests with 404.
+    pub async fn with_species_index(&self, names: &[&str]) -> &Self {
+        let resources = names
+            .iter()
+            .map(|name| serde_json::json!({ "name": name }))
+            .collect::<Vec<_>>();
+        Mock::given(method("GET"))
+            .and(path("api/v2/pokemon-species/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": names.len(),
+                "results": resources,
+            })))
+            .mount(&self.mock_poke_api)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("api/v2/generation/generation-i"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "name": "generation-i",
+                "pokemon_species": resources,
+            })))
+            .mount(&self.mock_poke_api)
+            .await;
+        self
+    }
+
+    /// Mock the evolution chain of Charizard, linked by all species of `with_poke_api`.
+    pub async fn with_evolution_chain(&self) -> &Self {
+        Mock::given(method("GET"))
+            .and(path("api/v2/evolution-chain/2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(CHARIZARD_EVOLUTION_CHAIN_RESPONSE, "application/json"),
+            )
+            .mount(&self.mock_poke_api)
+            .await;
+        self
+    }
+
+    /// Mock the PokéApi root which is used to probe its readiness.
+    pub async fn with_poke_api_root(&self) -> &Self {
+        Mock::given(method("GET"))
+            .and(path("api/v2/"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.mock_poke_api)
+            .await;
+        self
+    }
+
     /// Mock the PokéApi and return TOO_MANY_REQUESTS 429 for everything.
     pub async fn with_translate_rate_limit(&self) -> &Self {
         Mock::given(method("POST"))
@@ -0,0 +1,172 @@
+mod setup;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use pokespearify::config::{TlsConfig, TlsVersion};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+use reqwest::StatusCode;
+
+use setup::TestApp;
+
+/// A throwaway CA signing server and client certificates for a test.
+struct TestCa {
+    ca: Certificate,
+    dir: PathBuf,
+}
+
+impl TestCa {
+    fn new(dir: &Path) -> Self {
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        std::fs::create_dir_all(dir).unwrap();
+        TestCa {
+            ca: Certificate::from_params(params).unwrap(),
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn ca_pem(&self) -> String {
+        self.ca.serialize_pem().unwrap()
+    }
+
+    /// Issue a certificate for `localhost`, returns the certificate and key PEMs.
+    fn issue(&self) -> (String, String) {
+        let cert =
+            Certificate::from_params(CertificateParams::new(vec!["localhost".into()])).unwrap();
+        (
+            cert.serialize_pem_with_signer(&self.ca).unwrap(),
+            cert.serialize_private_key_pem(),
+        )
+    }
+
+    /// Issue a server certificate and write it to the test directory.
+    fn write_server_cert(&self) -> TlsConfig {
+        let (cert, key) = self.issue();
+        let cert_path = self.dir.join("server.crt");
+        let key_path = self.dir.join("server.key");
+        std::fs::write(&cert_path, cert).unwrap();
+        std::fs::write(&key_path, key).unwrap();
+        TlsConfig {
+            cert_path,
+            key_path,
+            min_version: TlsVersion::Tls12,
+            client_ca_path: None,
+            reload_interval_secs: 60,
+        }
+    }
+
+    fn client(&self, identity: Option<(String, String)>) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .add_root_certificate(
+                reqwest::Certificate::from_pem(self.ca_pem().as_bytes()).unwrap(),
+            );
+        if let Some((cert, key)) = identity {
+            let pem = format!("{}{}", cert, key);
+            builder = builder.identity(reqwest::Identity::from_pem(pem.as_bytes()).unwrap());
+        }
+        builder.build().unwrap()
+    }
+}
+
+fn test_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("pokespeare-tls-{}", uuid::Uuid::new_v4()))
+}
+
+#[actix_rt::test]
+async fn test_tls() {
+    let dir = test_dir();
+    let ca = TestCa::new(&dir);
+    let tls = ca.write_server_cert();
+    let app = TestApp::spawn_with(|config| config.tls = Some(tls)).await;
+
+    let url = format!("https://localhost:{}/healthz", app.inner().addr().port());
+    let resp = ca
+        .client(None)
+        .get(&url)
+        .send()
+        .await
+        .expect("The TLS endpoint is not working");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // plain HTTP is not served
+    reqwest::get(format!(
+        "http://localhost:{}/healthz",
+        app.inner().addr().port()
+    ))
+    .await
+    .expect_err("Plain HTTP should not be served");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_mutual_tls() {
+    let dir = test_dir();
+    let ca = TestCa::new(&dir);
+    let client_ca = TestCa::new(&dir.join("clients"));
+    let client_ca_path = dir.join("client_ca.crt");
+    std::fs::write(&client_ca_path, client_ca.ca_pem()).unwrap();
+    let mut tls = ca.write_server_cert();
+    tls.client_ca_path = Some(client_ca_path);
+    let app = TestApp::spawn_with(|config| config.tls = Some(tls)).await;
+
+    let url = format!("https://localhost:{}/healthz", app.inner().addr().port());
+    ca.client(None)
+        .get(&url)
+        .send()
+        .await
+        .expect_err("Clients without certificate should be rejected");
+
+    // a certificate from an unknown CA is rejected as well
+    ca.client(Some(ca.issue()))
+        .get(&url)
+        .send()
+        .await
+        .expect_err("Clients with an unknown certificate should be rejected");
+
+    let resp = ca
+        .client(Some(client_ca.issue()))
+        .get(&url)
+        .send()
+        .await
+        .expect("Clients with a valid certificate should be accepted");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_certificate_reload() {
+    let dir = test_dir();
+    let old_ca = TestCa::new(&dir);
+    let mut tls = old_ca.write_server_cert();
+    tls.reload_interval_secs = 1;
+    let app = TestApp::spawn_with(|config| config.tls = Some(tls)).await;
+
+    let url = format!("https://localhost:{}/healthz", app.inner().addr().port());
+    let resp = old_ca.client(None).get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // renew the certificate in place
+    let new_ca = TestCa::new(&dir);
+    new_ca.write_server_cert();
+    actix_rt::time::sleep(Duration::from_millis(1500)).await;
+
+    let resp = new_ca
+        .client(None)
+        .get(&url)
+        .send()
+        .await
+        .expect("The renewed certificate was not picked up");
+    assert_eq!(resp.status(), StatusCode::OK);
+    old_ca
+        .client(None)
+        .get(&url)
+        .send()
+        .await
+        .expect_err("The old certificate is still served");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}